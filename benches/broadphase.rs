@@ -0,0 +1,84 @@
+// compares the grid broadphase against the brute-force check, once for
+// the lasers and the ship against all asteroids and once for all pairs,
+// both on the wrapping arena
+// run with: cargo bench --bench broadphase
+use bevy::prelude::*;
+use rand::Rng;
+use std::time::{Duration, Instant};
+
+#[path = "../src/broadphase.rs"]
+#[allow(dead_code)]
+mod broadphase;
+#[path = "../src/torus.rs"]
+#[allow(dead_code)]
+mod torus;
+
+use broadphase::Broadphase;
+
+const MIN:Vec2 = Vec2::new(-11.0, -8.0);
+const MAX:Vec2 = Vec2::new(11.0, 8.0);
+const CELL:f32 = 2.0;
+const PROBES:usize = 12;
+const FRAMES:u32 = 100;
+
+type Body = (Entity, Vec3, f32);
+
+fn brute_force(bodies:&[Body], probes:&[Body]) -> usize {
+    let mut hits = 0;
+    for (probe, center, radius) in probes.iter() {
+        for (entity, other, other_radius) in bodies.iter() {
+            if probe != entity && torus::delta(*center, *other, MAX - MIN).length() < radius + other_radius {
+                hits += 1;
+            }
+        }
+    }
+    hits
+}
+
+fn grid(grid:&mut Broadphase, candidates:&mut Vec<Entity>, bodies:&[Body], probes:&[Body]) -> usize {
+    let mut hits = 0;
+    grid.clear();
+    for (entity, center, radius) in bodies.iter() {
+        grid.insert(*entity, *center, *radius);
+    }
+    for (probe, center, radius) in probes.iter() {
+        grid.query(*center, *radius, candidates);
+        hits += candidates.iter().filter(|entity| *entity != probe).count();
+    }
+    hits
+}
+
+fn time(mut run:impl FnMut() -> usize) -> (Duration, usize) {
+    let start = Instant::now();
+    let mut hits = 0;
+    for _ in 0..FRAMES {
+        hits = run();
+    }
+    (start.elapsed() / FRAMES, hits)
+}
+
+fn main() {
+    let mut rng = rand::thread_rng();
+    let mut random_body = |i:usize, radius:std::ops::Range<f32>| -> Body {
+        (Entity::from_raw(i as u32),
+         Vec3::new(rng.gen_range(MIN.x..MAX.x), 0.0, rng.gen_range(MIN.y..MAX.y)),
+         rng.gen_range(radius))
+    };
+    let mut broadphase = Broadphase::new(MIN, MAX, CELL);
+    let mut candidates = Vec::new();
+
+    for count in [10, 100, 500, 2000] {
+        let asteroids:Vec<Body> = (0..count).map(|i| random_body(i, 0.3..1.0)).collect();
+        let probes:Vec<Body> = (0..PROBES).map(|i| random_body(count + i, 0.8..0.81)).collect();
+
+        let (brute, brute_hits) = time(|| brute_force(&asteroids, &probes));
+        let (cells, grid_hits) = time(|| grid(&mut broadphase, &mut candidates, &asteroids, &probes));
+        assert_eq!(brute_hits, grid_hits);
+        println!("{:>5} asteroids, {} probes: brute force {:>10?}/frame, grid {:>10?}/frame", count, PROBES, brute, cells);
+
+        let (brute, brute_hits) = time(|| brute_force(&asteroids, &asteroids));
+        let (cells, grid_hits) = time(|| grid(&mut broadphase, &mut candidates, &asteroids, &asteroids));
+        assert_eq!(brute_hits, grid_hits);
+        println!("{:>5} asteroids, all pairs: brute force {:>10?}/frame, grid {:>10?}/frame", count, brute, cells);
+    }
+}
@@ -0,0 +1,296 @@
+use bevy::input::gamepad::{GamepadEvent, GamepadEventType};
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::config::GameConfig;
+use crate::fixed::{FixedSystem, FixedUpdate};
+use crate::storage;
+
+pub const BINDINGS_FILE:&str = "bindings.ron";
+
+pub struct ActionsPlugin;
+
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut App) {
+        let bindings = storage::load::<Bindings>(&storage::settings(), BINDINGS_FILE).unwrap_or_default();
+        app.insert_resource(bindings.completed())
+            .init_resource::<ActionState>()
+            .init_resource::<TickActions>()
+            .add_system_to_stage(CoreStage::PreUpdate, update_actions.label(UpdateActions).after(InputSystem))
+            .add_system_to_stage(FixedUpdate, sample_actions.label(SampleActions).before(FixedSystem::Simulate))
+            .add_system(gamepad_connections);
+    }
+}
+
+// other input sources add to the action state after this
+#[derive(SystemLabel)]
+pub struct UpdateActions;
+
+// TickActions hold this tick's actions after this
+#[derive(SystemLabel)]
+pub struct SampleActions;
+
+// what gameplay reacts to, independent of the device that triggered it
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
+    RotateLeft,
+    RotateRight,
+    Thrust,
+    Fire,
+    Hyperspace,
+    Pause,
+}
+
+impl Action {
+    pub const ALL:[Action; 6] = [
+        Action::RotateLeft,
+        Action::RotateRight,
+        Action::Thrust,
+        Action::Fire,
+        Action::Hyperspace,
+        Action::Pause,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::RotateLeft => "Rotate left",
+            Action::RotateRight => "Rotate right",
+            Action::Thrust => "Thrust",
+            Action::Fire => "Fire",
+            Action::Hyperspace => "Hyperspace",
+            Action::Pause => "Pause",
+        }
+    }
+}
+
+const ARROWS:[KeyCode; 6] = [KeyCode::Left, KeyCode::Right, KeyCode::Up, KeyCode::Space, KeyCode::Down, KeyCode::Escape];
+const WASD:[KeyCode; 6] = [KeyCode::A, KeyCode::D, KeyCode::W, KeyCode::J, KeyCode::S, KeyCode::P];
+const PAD:[GamepadButtonType; 6] = [
+    GamepadButtonType::DPadLeft,
+    GamepadButtonType::DPadRight,
+    GamepadButtonType::RightTrigger2,
+    GamepadButtonType::South,
+    GamepadButtonType::North,
+    GamepadButtonType::Start,
+];
+
+pub const SLOTS:usize = 2;
+
+// keys per action, the first slot defaults to the arrow layout, the second to WASD.
+// every connected gamepad drives the actions through its buttons,
+// the left stick rotates as well
+#[derive(Resource, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bindings{
+    pub keys:BTreeMap<Action, [Option<KeyCode>; SLOTS]>,
+    pub buttons:BTreeMap<Action, GamepadButtonType>,
+}
+
+impl Default for Bindings{
+    fn default() -> Self {
+        let keys = Action::ALL.iter().enumerate()
+            .map(|(i, action)| (*action, [Some(ARROWS[i]), Some(WASD[i])]))
+            .collect();
+        let buttons = Action::ALL.iter().zip(PAD).map(|(action, button)| (*action, button)).collect();
+        Self { keys, buttons }
+    }
+}
+
+impl Bindings {
+    // actions missing from a saved file get their default keys
+    fn completed(mut self) -> Self {
+        let default = Bindings::default();
+        for (action, keys) in default.keys {
+            self.keys.entry(action).or_insert(keys);
+        }
+        for (action, button) in default.buttons {
+            self.buttons.entry(action).or_insert(button);
+        }
+        self
+    }
+
+    pub fn key(&self, action:Action, slot:usize) -> Option<KeyCode> {
+        self.keys.get(&action).and_then(|keys| keys[slot])
+    }
+
+    // a key drives a single action, binding it elsewhere moves it
+    pub fn bind(&mut self, action:Action, slot:usize, key:KeyCode) {
+        for keys in self.keys.values_mut() {
+            for bound in keys.iter_mut() {
+                if *bound == Some(key) {
+                    *bound = None;
+                }
+            }
+        }
+        self.keys.entry(action).or_insert([None; SLOTS])[slot] = Some(key);
+    }
+
+    pub fn clear(&mut self, action:Action, slot:usize) {
+        if let Some(keys) = self.keys.get_mut(&action) {
+            keys[slot] = None;
+        }
+    }
+}
+
+// actions active this frame, rebuilt from the bindings before Update.
+// keys press an action fully, sticks and triggers partly
+#[derive(Resource, Default)]
+pub struct ActionState{
+    values:HashMap<Action, f32>,
+    just_pressed:HashSet<Action>,
+    // presses since the last tick, so none is lost or doubled
+    // when a frame runs zero or several ticks
+    unsampled:BTreeSet<Action>,
+}
+
+impl ActionState {
+    // the strongest source wins
+    pub fn press(&mut self, action:Action, value:f32) {
+        if value > 0.0 {
+            let current = self.values.entry(action).or_insert(0.0);
+            *current = current.max(value.min(1.0));
+        }
+    }
+
+    pub fn trigger(&mut self, action:Action) {
+        self.just_pressed.insert(action);
+        self.unsampled.insert(action);
+    }
+
+    // like TickActions::just_pressed, but later systems of the frame no longer see it.
+    // keeps a state change from being undone by the new state's systems
+    pub fn consume(&mut self, action:Action) -> bool {
+        self.just_pressed.remove(&action)
+    }
+}
+
+// the actions as seen by one simulation tick, gameplay in FixedUpdate reads these.
+// kept ordered, so recording the same run writes the same file
+#[derive(Resource, Default, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TickActions{
+    values:BTreeMap<Action, f32>,
+    just_pressed:BTreeSet<Action>,
+}
+
+impl TickActions {
+    pub fn pressed(&self, action:Action) -> bool {
+        self.value(action) > 0.0
+    }
+
+    pub fn value(&self, action:Action) -> f32 {
+        self.values.get(&action).copied().unwrap_or(0.0)
+    }
+
+    pub fn just_pressed(&self, action:Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+}
+
+#[cfg(test)]
+impl TickActions {
+    pub fn new(values:&[(Action, f32)], just_pressed:&[Action]) -> Self {
+        Self {
+            values: values.iter().copied().collect(),
+            just_pressed: just_pressed.iter().copied().collect(),
+        }
+    }
+}
+
+fn sample_actions(
+    mut actions: ResMut<ActionState>,
+    mut tick: ResMut<TickActions>,
+){
+    tick.values = actions.values.iter().map(|(action, value)| (*action, *value)).collect();
+    tick.just_pressed = std::mem::take(&mut actions.unsampled);
+}
+
+// 0 inside the dead zone, then rising smoothly to 1
+pub fn dead_zone(value:f32, dead_zone:f32) -> f32 {
+    ((value.abs() - dead_zone) / (1.0 - dead_zone)).clamp(0.0, 1.0)
+}
+
+fn update_actions(
+    config: Res<GameConfig>,
+    bindings: Res<Bindings>,
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_button_axes: Res<Axis<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    mut actions: ResMut<ActionState>,
+){
+    actions.values.clear();
+    actions.just_pressed.clear();
+    for (action, keys) in bindings.keys.iter() {
+        for key in keys.iter().flatten() {
+            if keyboard_input.pressed(*key) {
+                actions.press(*action, 1.0);
+            }
+            if keyboard_input.just_pressed(*key) {
+                actions.trigger(*action);
+            }
+        }
+    }
+    for gamepad in gamepads.iter() {
+        for (action, button_type) in bindings.buttons.iter() {
+            let button = GamepadButton::new(gamepad, *button_type);
+            // triggers report their pressure, plain buttons 0 or 1
+            actions.press(*action, gamepad_button_axes.get(button).unwrap_or(0.0));
+            if gamepad_buttons.just_pressed(button) {
+                actions.trigger(*action);
+            }
+        }
+        let x = gamepad_axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX)).unwrap_or(0.0);
+        let turn = dead_zone(x, config.stick_dead_zone);
+        actions.press(if x < 0.0 { Action::RotateLeft } else { Action::RotateRight }, turn);
+    }
+}
+
+fn gamepad_connections(
+    mut events: EventReader<GamepadEvent>,
+){
+    for event in events.iter() {
+        match &event.event_type {
+            GamepadEventType::Connected(info) => info!("gamepad {} connected: {}", event.gamepad.id, info.name),
+            GamepadEventType::Disconnected => info!("gamepad {} disconnected", event.gamepad.id),
+            _ => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_a_key_moves_it_off_other_actions() {
+        let mut bindings = Bindings::default();
+        bindings.bind(Action::Fire, 1, KeyCode::Left);
+        assert_eq!(bindings.key(Action::Fire, 1), Some(KeyCode::Left));
+        assert_eq!(bindings.key(Action::RotateLeft, 0), None);
+        // the key it replaced is free now, the other slot is untouched
+        assert_eq!(bindings.key(Action::Fire, 0), Some(KeyCode::Space));
+        assert!(bindings.keys.values().flatten().all(|key| *key != Some(KeyCode::J)));
+        // binding it again to the same slot keeps it
+        bindings.bind(Action::Fire, 1, KeyCode::Left);
+        assert_eq!(bindings.key(Action::Fire, 1), Some(KeyCode::Left));
+    }
+
+    #[test]
+    fn saved_file_gets_missing_actions_filled_in() {
+        let saved:Bindings = ron::from_str("(keys:{Fire:(Some(F), None)}, buttons:{Thrust:East})").unwrap();
+        let bindings = saved.completed();
+        let default = Bindings::default();
+        assert_eq!(bindings.keys[&Action::Fire], [Some(KeyCode::F), None]);
+        assert_eq!(bindings.buttons[&Action::Thrust], GamepadButtonType::East);
+        for action in Action::ALL.into_iter().filter(|action| *action != Action::Fire) {
+            assert_eq!(bindings.keys[&action], default.keys[&action]);
+        }
+        for action in Action::ALL.into_iter().filter(|action| *action != Action::Thrust) {
+            assert_eq!(bindings.buttons[&action], default.buttons[&action]);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use bevy::prelude::*;
+use bevy::render::mesh::VertexAttributeValues;
+
+const SUBDIVISIONS:usize = 3;
+const OCTAVES:i32 = 3;
+const FREQUENCY:f32 = 1.5;
+
+// icosphere displaced along its normals by seeded noise,
+// returns the mesh and its bounding radius
+pub fn asteroid_mesh(seed:u64, radius:f32, roughness:f32) -> (Mesh, f32) {
+    let mut mesh = Mesh::from(shape::Icosphere { radius: 1.0, subdivisions: SUBDIVISIONS });
+    let mut bounding_radius:f32 = 0.0;
+    if let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION) {
+        for position in positions.iter_mut() {
+            let direction = Vec3::from(*position).normalize();
+            let displacement = 1.0 + roughness * fractal_noise(seed, direction * FREQUENCY);
+            let displaced = direction * radius * displacement.max(0.1);
+            bounding_radius = bounding_radius.max(displaced.length());
+            *position = displaced.into();
+        }
+    }
+    // flat shading gives the rocky, faceted look
+    mesh.duplicate_vertices();
+    mesh.compute_flat_normals();
+    (mesh, bounding_radius)
+}
+
+// sum of value noise octaves, roughly in -1..1
+fn fractal_noise(seed:u64, point:Vec3) -> f32 {
+    let mut value = 0.0;
+    let mut amplitude = 0.5;
+    let mut frequency = 1.0;
+    for octave in 0..OCTAVES {
+        value += amplitude * value_noise(seed.wrapping_add(octave as u64), point * frequency);
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    value / (1.0 - amplitude * 2.0)
+}
+
+fn value_noise(seed:u64, point:Vec3) -> f32 {
+    let cell = point.floor();
+    let t = point - cell;
+    let t = t * t * (Vec3::splat(3.0) - 2.0 * t);
+    let (x, y, z) = (cell.x as i32, cell.y as i32, cell.z as i32);
+
+    let corner = |dx:i32, dy:i32, dz:i32| lattice(seed, x + dx, y + dy, z + dz);
+    let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), t.x);
+    let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), t.x);
+    let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), t.x);
+    let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), t.x);
+    lerp(lerp(x00, x10, t.y), lerp(x01, x11, t.y), t.z)
+}
+
+// hashed lattice value in -1..1
+fn lattice(seed:u64, x:i32, y:i32, z:i32) -> f32 {
+    let mut h = seed
+        ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (z as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xC4CE_B9FE_1A85_EC53);
+    h ^= h >> 33;
+    (h >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+}
+
+fn lerp(a:f32, b:f32, t:f32) -> f32 {
+    a + (b - a) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(mesh:&Mesh) -> Vec<[f32; 3]> {
+        match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float32x3(positions)) => positions.clone(),
+            _ => panic!("mesh without positions"),
+        }
+    }
+
+    #[test]
+    fn same_seed_same_mesh() {
+        let (mesh, radius) = asteroid_mesh(7, 1.5, 0.35);
+        let (again, again_radius) = asteroid_mesh(7, 1.5, 0.35);
+        assert_eq!(positions(&mesh), positions(&again));
+        assert_eq!(radius, again_radius);
+        let (other, _) = asteroid_mesh(8, 1.5, 0.35);
+        assert_ne!(positions(&mesh), positions(&other));
+    }
+
+    #[test]
+    fn radius_stays_in_bounds() {
+        for seed in 0..32 {
+            for roughness in [0.0, 0.35, 0.8] {
+                let radius = 2.0;
+                let (mesh, bounding_radius) = asteroid_mesh(seed, radius, roughness);
+                let lengths:Vec<f32> = positions(&mesh).iter().map(|p| Vec3::from(*p).length()).collect();
+                let longest = lengths.iter().copied().fold(0.0, f32::max);
+                assert!((bounding_radius - longest).abs() < 1e-5);
+                for length in lengths {
+                    assert!(length >= radius * (1.0 - roughness).max(0.1) - 1e-4, "seed {} too small: {}", seed, length);
+                    assert!(length <= radius * (1.0 + roughness) + 1e-4, "seed {} too large: {}", seed, length);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use bevy::prelude::*;
+
+use crate::torus;
+
+// uniform grid over the wrapping arena in the xz plane, rebuilt every frame.
+// entities are stored in every cell their bounding circle touches,
+// cells past one edge continue on the opposite edge
+#[derive(Resource)]
+pub struct Broadphase{
+    min:Vec2,
+    size:Vec2,
+    cell:Vec2,
+    cols:usize,
+    rows:usize,
+    cells:Vec<Vec<(Entity, Vec3, f32)>>,
+}
+
+impl Broadphase {
+    pub fn new(min:Vec2, max:Vec2, cell_size:f32) -> Self {
+        let size = max - min;
+        // whole cells only, so wrapped indices line up with the arena edges
+        let cols = ((size.x / cell_size).floor() as usize).max(1);
+        let rows = ((size.y / cell_size).floor() as usize).max(1);
+        Self {
+            min,
+            size,
+            cell: Vec2::new(size.x / cols as f32, size.y / rows as f32),
+            cols,
+            rows,
+            cells: vec![Vec::new(); cols * rows],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            cell.clear();
+        }
+    }
+
+    pub fn insert(&mut self, entity:Entity, center:Vec3, radius:f32) {
+        let center = torus::wrap(center, self.min, self.size);
+        for row in Self::span(center.z, radius, self.min.y, self.cell.y, self.rows) {
+            for col in Self::span(center.x, radius, self.min.x, self.cell.x, self.cols) {
+                self.cells[row * self.cols + col].push((entity, center, radius));
+            }
+        }
+    }
+
+    // entities whose bounding circle overlaps the given one across the
+    // wrap boundary, without duplicates
+    pub fn query(&self, center:Vec3, radius:f32, out:&mut Vec<Entity>) {
+        out.clear();
+        let center = torus::wrap(center, self.min, self.size);
+        for row in Self::span(center.z, radius, self.min.y, self.cell.y, self.rows) {
+            for col in Self::span(center.x, radius, self.min.x, self.cell.x, self.cols) {
+                for (entity, other_center, other_radius) in self.cells[row * self.cols + col].iter() {
+                    let reach = radius + other_radius;
+                    if torus::delta(center, *other_center, self.size).length_squared() < reach * reach {
+                        out.push(*entity);
+                    }
+                }
+            }
+        }
+        out.sort_unstable();
+        out.dedup();
+    }
+
+    // wrapped cell indices covered by value - radius ..= value + radius
+    fn span(value:f32, radius:f32, min:f32, cell:f32, count:usize) -> impl Iterator<Item = usize> {
+        let first = ((value - radius - min) / cell).floor() as i64;
+        let last = ((value + radius - min) / cell).floor() as i64;
+        let (first, len) = if last - first + 1 >= count as i64 { (0, count as i64) } else { (first, last - first + 1) };
+        (first..first + len).map(move |i| i.rem_euclid(count as i64) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 20 by 20 around the origin, 2 wide cells
+    fn grid() -> Broadphase {
+        Broadphase::new(Vec2::splat(-10.0), Vec2::splat(10.0), 2.0)
+    }
+
+    fn query(broadphase:&Broadphase, center:Vec3, radius:f32) -> Vec<Entity> {
+        let mut out = Vec::new();
+        broadphase.query(center, radius, &mut out);
+        out
+    }
+
+    #[test]
+    fn finds_neighbours_across_the_edges() {
+        let mut broadphase = grid();
+        broadphase.insert(Entity::from_raw(0), Vec3::new(9.8, 0.0, 0.0), 0.5);
+        broadphase.insert(Entity::from_raw(1), Vec3::new(0.0, 0.0, -9.8), 0.5);
+        broadphase.insert(Entity::from_raw(2), Vec3::new(9.8, 0.0, 9.8), 0.5);
+        assert_eq!(query(&broadphase, Vec3::new(-9.8, 0.0, 0.0), 0.5), vec![Entity::from_raw(0)]);
+        assert_eq!(query(&broadphase, Vec3::new(0.0, 0.0, 9.8), 0.5), vec![Entity::from_raw(1)]);
+        // diagonally over the corner
+        assert_eq!(query(&broadphase, Vec3::new(-9.8, 0.0, -9.8), 0.5), vec![Entity::from_raw(2)]);
+        // far from all of them
+        assert!(query(&broadphase, Vec3::new(0.0, 0.0, 0.0), 0.5).is_empty());
+    }
+
+    #[test]
+    fn radius_wider_than_the_arena_covers_every_cell_once() {
+        let mut broadphase = grid();
+        broadphase.insert(Entity::from_raw(0), Vec3::ZERO, 50.0);
+        assert!(broadphase.cells.iter().all(|cell| cell.len() == 1));
+        broadphase.insert(Entity::from_raw(1), Vec3::new(7.0, 0.0, -3.0), 0.1);
+        assert_eq!(query(&broadphase, Vec3::new(-5.0, 0.0, 5.0), 30.0), vec![Entity::from_raw(0), Entity::from_raw(1)]);
+    }
+
+    #[test]
+    fn entity_in_many_cells_is_returned_once() {
+        let mut broadphase = grid();
+        // spans nine cells, and the query covers all of them
+        broadphase.insert(Entity::from_raw(3), Vec3::new(1.0, 0.0, 1.0), 1.5);
+        broadphase.insert(Entity::from_raw(4), Vec3::new(-1.0, 0.0, -1.0), 1.5);
+        assert_eq!(query(&broadphase, Vec3::ZERO, 3.0), vec![Entity::from_raw(3), Entity::from_raw(4)]);
+    }
+}
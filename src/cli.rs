@@ -0,0 +1,80 @@
+use bevy::prelude::*;
+use std::path::{Path, PathBuf};
+
+use crate::replay::Recording;
+
+// options given on the command line, the web build has none
+#[derive(Resource, Default, Debug)]
+pub struct CommandLine{
+    pub seed:Option<u64>,
+    // write the inputs of each run to this file
+    pub record:Option<PathBuf>,
+    // the run to play, read from the file given
+    pub replay:Option<Recording>,
+}
+
+impl CommandLine {
+    pub fn parse(mut args:impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut command_line = CommandLine::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => {
+                    let value = args.next().ok_or("--seed needs a number")?;
+                    command_line.seed = Some(value.parse().map_err(|_| format!("--seed needs a number, got {}", value))?);
+                },
+                "--record" => {
+                    command_line.record = Some(args.next().ok_or("--record needs a file")?.into());
+                },
+                "--replay" => {
+                    // a replay that can't be read is an error like a bad argument
+                    let path = args.next().ok_or("--replay needs a file")?;
+                    command_line.replay = Some(Recording::load(Path::new(&path))?);
+                },
+                _ => return Err(format!("unknown argument {}", arg)),
+            }
+        }
+        if command_line.record.is_some() && command_line.replay.is_some() {
+            return Err("--record and --replay can't be used together".to_string());
+        }
+        Ok(command_line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args:&[&str]) -> Result<CommandLine, String> {
+        CommandLine::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn record_takes_a_file() {
+        let command_line = parse(&["--seed", "7", "--record", "run.ron"]).unwrap();
+        assert_eq!(command_line.seed, Some(7));
+        assert_eq!(command_line.record, Some(PathBuf::from("run.ron")));
+        assert!(command_line.replay.is_none());
+        assert!(parse(&["--record"]).is_err());
+    }
+
+    #[test]
+    fn replay_reads_the_recording() {
+        let path = std::env::temp_dir().join(format!("bevyastro-cli-{}.ron", std::process::id()));
+        std::fs::write(&path, ron::to_string(&Recording::default()).unwrap()).unwrap();
+        let command_line = parse(&["--replay", path.to_str().unwrap()]);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(command_line.unwrap().replay, Some(Recording::default()));
+        // a missing file is reported like a bad argument
+        assert!(parse(&["--replay", path.to_str().unwrap()]).unwrap_err().contains("could not read"));
+        assert!(parse(&["--replay"]).is_err());
+    }
+
+    #[test]
+    fn record_and_replay_exclude_each_other() {
+        let path = std::env::temp_dir().join(format!("bevyastro-cli-both-{}.ron", std::process::id()));
+        std::fs::write(&path, ron::to_string(&Recording::default()).unwrap()).unwrap();
+        let both = parse(&["--record", "run.ron", "--replay", path.to_str().unwrap()]);
+        std::fs::remove_file(&path).unwrap();
+        assert!(both.is_err());
+    }
+}
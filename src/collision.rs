@@ -0,0 +1,205 @@
+use bevy::prelude::*;
+
+// collision shape in the entity's local space, scaled by its transform
+#[derive(Component, Clone, Debug)]
+pub enum Collider {
+    Sphere{radius:f32},
+    // capsule along the local z axis
+    Capsule{half_length:f32, radius:f32},
+    Compound(Vec<(Vec3, Collider)>),
+}
+
+// world space primitive: a segment with a radius, spheres have a == b
+#[derive(Clone, Copy, Debug)]
+pub struct Part{
+    pub a:Vec3,
+    pub b:Vec3,
+    pub radius:f32,
+}
+
+impl Collider {
+    pub fn parts(&self, transform:&Transform) -> Vec<Part> {
+        let mut parts = Vec::new();
+        self.collect_parts(transform, Vec3::ZERO, &mut parts);
+        parts
+    }
+
+    fn collect_parts(&self, transform:&Transform, offset:Vec3, parts:&mut Vec<Part>) {
+        let scale = transform.scale.max_element();
+        match self {
+            Collider::Sphere{radius} => {
+                let center = transform.transform_point(offset);
+                parts.push(Part{a:center, b:center, radius:radius * scale});
+            },
+            Collider::Capsule{half_length, radius} => {
+                parts.push(Part{
+                    a:transform.transform_point(offset - Vec3::Z * *half_length),
+                    b:transform.transform_point(offset + Vec3::Z * *half_length),
+                    radius:radius * scale,
+                });
+            },
+            Collider::Compound(children) => {
+                for (child_offset, child) in children {
+                    child.collect_parts(transform, offset + *child_offset, parts);
+                }
+            },
+        }
+    }
+
+    // radius of a sphere around the local origin containing the whole shape
+    pub fn bounding_radius(&self) -> f32 {
+        match self {
+            Collider::Sphere{radius} => *radius,
+            Collider::Capsule{half_length, radius} => half_length + radius,
+            Collider::Compound(children) => children.iter()
+                .map(|(offset, child)| offset.length() + child.bounding_radius())
+                .fold(0.0, f32::max),
+        }
+    }
+}
+
+pub fn overlaps(a:&Collider, a_transform:&Transform, b:&Collider, b_transform:&Transform) -> bool {
+    let b_parts = b.parts(b_transform);
+    a.parts(a_transform).iter().any(|pa| {
+        b_parts.iter().any(|pb| {
+            let reach = pa.radius + pb.radius;
+            segment_distance_squared(pa.a, pa.b, pb.a, pb.b) < reach * reach
+        })
+    })
+}
+
+// checks `a` moved by `motion` up to its current transform against `b`,
+// returns how far along the motion (0..1) they first touch
+pub fn swept_overlap(a:&Collider, a_transform:&Transform, motion:Vec3, b:&Collider, b_transform:&Transform) -> Option<f32> {
+    let b_parts = b.parts(b_transform);
+    let mut first:Option<f32> = None;
+    for pa in a.parts(a_transform) {
+        for pb in b_parts.iter() {
+            let reach = pa.radius + pb.radius;
+            let distance_squared = |t:f32| {
+                let offset = motion * (t - 1.0);
+                segment_distance_squared(pa.a + offset, pa.b + offset, pb.a, pb.b)
+            };
+            if let Some(t) = first_below(distance_squared, reach * reach) {
+                first = Some(first.map_or(t, |first| first.min(t)));
+            }
+        }
+    }
+    first
+}
+
+const SEARCH_STEPS:usize = 32;
+
+// first t in 0..1 where `f` drops below `limit`. `f` has to be convex, which
+// the distance between two shapes is while one of them moves along a line
+fn first_below(f:impl Fn(f32) -> f32, limit:f32) -> Option<f32> {
+    if f(0.0) < limit {
+        return Some(0.0);
+    }
+    // the closest point by ternary search, then the entry before it by bisection
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..SEARCH_STEPS {
+        let (m1, m2) = (low + (high - low) / 3.0, high - (high - low) / 3.0);
+        if f(m1) <= f(m2) {
+            high = m2;
+        } else {
+            low = m1;
+        }
+    }
+    let closest = (low + high) * 0.5;
+    if f(closest) >= limit {
+        return None;
+    }
+    let (mut outside, mut inside) = (0.0, closest);
+    for _ in 0..SEARCH_STEPS {
+        let middle = (outside + inside) * 0.5;
+        if f(middle) < limit {
+            inside = middle;
+        } else {
+            outside = middle;
+        }
+    }
+    Some(inside)
+}
+
+// squared distance between the closest points of segments p1-q1 and p2-q2
+pub fn segment_distance_squared(p1:Vec3, q1:Vec3, p2:Vec3, q2:Vec3) -> f32 {
+    let d1 = q1 - p1;
+    let d2 = q2 - p2;
+    let r = p1 - p2;
+    let a = d1.length_squared();
+    let e = d2.length_squared();
+    let f = d2.dot(r);
+
+    let (s, t) = if a <= f32::EPSILON && e <= f32::EPSILON {
+        (0.0, 0.0)
+    } else if a <= f32::EPSILON {
+        (0.0, (f / e).clamp(0.0, 1.0))
+    } else {
+        let c = d1.dot(r);
+        if e <= f32::EPSILON {
+            ((-c / a).clamp(0.0, 1.0), 0.0)
+        } else {
+            let b = d1.dot(d2);
+            let denom = a * e - b * b;
+            let mut s = if denom > f32::EPSILON { ((b * f - c * e) / denom).clamp(0.0, 1.0) } else { 0.0 };
+            let mut t = (b * s + f) / e;
+            if t < 0.0 {
+                t = 0.0;
+                s = (-c / a).clamp(0.0, 1.0);
+            } else if t > 1.0 {
+                t = 1.0;
+                s = ((b - c) / a).clamp(0.0, 1.0);
+            }
+            (s, t)
+        }
+    };
+    (p1 + d1 * s).distance_squared(p2 + d2 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a laser point moving 10 along +z, ending at z = 10
+    fn sweep(b:&Collider, b_transform:&Transform) -> Option<f32> {
+        let laser = Collider::Sphere{radius:0.1};
+        swept_overlap(&laser, &Transform::from_xyz(0.0, 0.0, 10.0), Vec3::Z * 10.0, b, b_transform)
+    }
+
+    #[test]
+    fn fast_laser_does_not_tunnel() {
+        let t = sweep(&Collider::Sphere{radius:0.2}, &Transform::from_xyz(0.0, 0.0, 5.0));
+        assert!(t.is_some());
+    }
+
+    #[test]
+    fn miss_beside_the_path() {
+        assert_eq!(sweep(&Collider::Sphere{radius:0.5}, &Transform::from_xyz(1.0, 0.0, 5.0)), None);
+    }
+
+    #[test]
+    fn returns_the_entry_time() {
+        // touches at z = 5 - 1 - 0.1
+        let t = sweep(&Collider::Sphere{radius:1.0}, &Transform::from_xyz(0.0, 0.0, 5.0)).unwrap();
+        assert!((t - 0.39).abs() < 1e-4, "t = {}", t);
+        // off center the entry comes later than the closest approach would suggest
+        let t = sweep(&Collider::Sphere{radius:1.0}, &Transform::from_xyz(0.8, 0.0, 5.0)).unwrap();
+        let entry = (5.0 - (1.1f32 * 1.1 - 0.8 * 0.8).sqrt()) / 10.0;
+        assert!((t - entry).abs() < 1e-4, "t = {}, expected {}", t, entry);
+    }
+
+    #[test]
+    fn large_rock_behind_a_small_one_is_hit_first() {
+        // the big rock's center is further along, but its surface is reached first
+        let big = sweep(&Collider::Sphere{radius:3.0}, &Transform::from_xyz(0.0, 0.0, 6.0)).unwrap();
+        let small = sweep(&Collider::Sphere{radius:0.5}, &Transform::from_xyz(0.0, 0.0, 4.0)).unwrap();
+        assert!(big < small, "big {} small {}", big, small);
+    }
+
+    #[test]
+    fn already_overlapping_starts_at_zero() {
+        let t = sweep(&Collider::Sphere{radius:1.0}, &Transform::from_xyz(0.0, 0.0, 0.5));
+        assert_eq!(t, Some(0.0));
+    }
+}
@@ -0,0 +1,312 @@
+use bevy::asset::{AssetLoader, LoadContext, LoadState, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::fmt;
+
+const CONFIG_PATH:&str = "config.ron";
+
+pub struct ConfigPlugin;
+
+impl Plugin for ConfigPlugin {
+    fn build(&self, app: &mut App) {
+        // the defaults are used until the file has been loaded
+        app.insert_resource(GameConfig::default())
+            .init_resource::<ConfigLoaded>()
+            .add_asset::<GameConfig>()
+            .init_asset_loader::<GameConfigLoader>()
+            .add_startup_system(load_config)
+            .add_system_to_stage(CoreStage::PreUpdate, apply_config);
+    }
+}
+
+// gameplay tunables, read from assets/config.ron.
+// missing fields keep their default
+#[derive(Resource, Serialize, Deserialize, TypeUuid, Clone, PartialEq, Debug)]
+#[uuid = "5c1d3f2e-8a4b-4c6d-9e7f-0a1b2c3d4e5f"]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig{
+    pub turn_speed:f32,
+    pub ship_thrust:f32,
+    pub friction:f32,
+    pub ship_max_speed:f32,
+    pub ships:i32,
+    pub invulnerable_time:f32,
+    // stick deflection ignored around the center, 0..1
+    pub stick_dead_zone:f32,
+    pub laser_speed:f32,
+    pub laser_lifetime:f32,
+    pub max_laser:i32,
+    pub asteroid_size:f32,
+    pub asteroid_roughness:f32,
+    pub shake_variance:f32,
+    pub shake_decay:f32,
+    // the arena bounds follow from what the camera sees at this height
+    pub camera_height:f32,
+    // fixed seed for every run, a random one is picked and logged otherwise
+    pub seed:Option<u64>,
+    pub wave:WaveCurve,
+}
+
+impl Default for GameConfig{
+    fn default() -> Self {
+        Self {
+            turn_speed:PI,
+            ship_thrust:1.0,
+            friction:0.8,
+            ship_max_speed:6.0,
+            ships:3,
+            invulnerable_time:2.0,
+            stick_dead_zone:0.2,
+            laser_speed:8.0,
+            laser_lifetime:1.0,
+            max_laser:10,
+            asteroid_size:0.5,
+            asteroid_roughness:0.35,
+            shake_variance:0.5,
+            shake_decay:1.5,
+            camera_height:20.0,
+            seed:None,
+            wave:WaveCurve::default(),
+        }
+    }
+}
+
+// asteroids per wave grow linearly, speed grows linearly up to a cap
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct WaveCurve{
+    pub base_count:i32,
+    pub count_per_wave:i32,
+    pub max_count:i32,
+    pub base_speed:f32,
+    pub speed_per_wave:f32,
+    pub max_speed:f32,
+}
+
+impl Default for WaveCurve{
+    fn default() -> Self {
+        Self {
+            base_count:3,
+            count_per_wave:1,
+            max_count:12,
+            base_speed:1.0,
+            speed_per_wave:0.25,
+            max_speed:3.0,
+        }
+    }
+}
+
+impl WaveCurve{
+    pub fn count(&self, wave:i32) -> i32 {
+        (self.base_count + self.count_per_wave * (wave - 1)).min(self.max_count)
+    }
+
+    pub fn speed(&self, wave:i32) -> f32 {
+        (self.base_speed + self.speed_per_wave * (wave - 1) as f32).min(self.max_speed)
+    }
+}
+
+#[derive(Debug)]
+pub struct ConfigError{
+    field:&'static str,
+    requirement:&'static str,
+    value:String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid {}: `{}` must be {}, got {}", CONFIG_PATH, self.field, self.requirement, self.value)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn check(field:&'static str, valid:bool, requirement:&'static str, value:impl fmt::Display) -> Result<(), ConfigError> {
+    if valid {
+        Ok(())
+    } else {
+        Err(ConfigError{field, requirement, value:value.to_string()})
+    }
+}
+
+impl GameConfig {
+    // "field: old -> new" for every field that differs
+    pub fn changes(&self, new:&GameConfig) -> Vec<String> {
+        let mut changes = Vec::new();
+        let mut field = |name:&str, old:&dyn fmt::Debug, new:&dyn fmt::Debug| {
+            let (old, new) = (format!("{:?}", old), format!("{:?}", new));
+            if old != new {
+                changes.push(format!("{}: {} -> {}", name, old, new));
+            }
+        };
+        field("turn_speed", &self.turn_speed, &new.turn_speed);
+        field("ship_thrust", &self.ship_thrust, &new.ship_thrust);
+        field("friction", &self.friction, &new.friction);
+        field("ship_max_speed", &self.ship_max_speed, &new.ship_max_speed);
+        field("ships", &self.ships, &new.ships);
+        field("invulnerable_time", &self.invulnerable_time, &new.invulnerable_time);
+        field("stick_dead_zone", &self.stick_dead_zone, &new.stick_dead_zone);
+        field("laser_speed", &self.laser_speed, &new.laser_speed);
+        field("laser_lifetime", &self.laser_lifetime, &new.laser_lifetime);
+        field("max_laser", &self.max_laser, &new.max_laser);
+        field("asteroid_size", &self.asteroid_size, &new.asteroid_size);
+        field("asteroid_roughness", &self.asteroid_roughness, &new.asteroid_roughness);
+        field("shake_variance", &self.shake_variance, &new.shake_variance);
+        field("shake_decay", &self.shake_decay, &new.shake_decay);
+        field("camera_height", &self.camera_height, &new.camera_height);
+        field("seed", &self.seed, &new.seed);
+        field("wave.base_count", &self.wave.base_count, &new.wave.base_count);
+        field("wave.count_per_wave", &self.wave.count_per_wave, &new.wave.count_per_wave);
+        field("wave.max_count", &self.wave.max_count, &new.wave.max_count);
+        field("wave.base_speed", &self.wave.base_speed, &new.wave.base_speed);
+        field("wave.speed_per_wave", &self.wave.speed_per_wave, &new.wave.speed_per_wave);
+        field("wave.max_speed", &self.wave.max_speed, &new.wave.max_speed);
+        changes
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        // comparisons are written so NaN fails them
+        check("turn_speed", self.turn_speed > 0.0, "greater than 0", self.turn_speed)?;
+        check("ship_thrust", self.ship_thrust > 0.0, "greater than 0", self.ship_thrust)?;
+        check("friction", self.friction >= 0.0, "at least 0", self.friction)?;
+        check("ship_max_speed", self.ship_max_speed > 0.0, "greater than 0", self.ship_max_speed)?;
+        check("ships", self.ships >= 1, "at least 1", self.ships)?;
+        check("invulnerable_time", self.invulnerable_time >= 0.0, "at least 0", self.invulnerable_time)?;
+        check("stick_dead_zone", (0.0..1.0).contains(&self.stick_dead_zone), "in 0..1", self.stick_dead_zone)?;
+        check("laser_speed", self.laser_speed > 0.0, "greater than 0", self.laser_speed)?;
+        check("laser_lifetime", self.laser_lifetime > 0.0, "greater than 0", self.laser_lifetime)?;
+        check("max_laser", self.max_laser >= 0, "at least 0", self.max_laser)?;
+        check("asteroid_size", self.asteroid_size > 0.0, "greater than 0", self.asteroid_size)?;
+        check("asteroid_roughness", (0.0..1.0).contains(&self.asteroid_roughness), "in 0..1", self.asteroid_roughness)?;
+        check("shake_variance", self.shake_variance >= 0.0, "at least 0", self.shake_variance)?;
+        check("shake_decay", self.shake_decay > 0.0, "greater than 0", self.shake_decay)?;
+        check("camera_height", self.camera_height > 1.0, "greater than 1", self.camera_height)?;
+        let wave = &self.wave;
+        check("wave.base_count", wave.base_count >= 1, "at least 1", wave.base_count)?;
+        check("wave.count_per_wave", wave.count_per_wave >= 0, "at least 0", wave.count_per_wave)?;
+        check("wave.max_count", wave.max_count >= wave.base_count, "at least wave.base_count", wave.max_count)?;
+        check("wave.base_speed", wave.base_speed > 0.0, "greater than 0", wave.base_speed)?;
+        check("wave.speed_per_wave", wave.speed_per_wave >= 0.0, "at least 0", wave.speed_per_wave)?;
+        check("wave.max_speed", wave.max_speed >= wave.base_speed, "at least wave.base_speed", wave.max_speed)?;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct GameConfigLoader;
+
+impl AssetLoader for GameConfigLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let config:GameConfig = ron::de::from_bytes(bytes)?;
+            config.validate()?;
+            load_context.set_default_asset(LoadedAsset::new(config));
+            Ok(())
+        })
+    }
+
+    // the asset server looks up what follows each dot of the file name,
+    // for config.ron that's just ron. it's the only ron file loaded as an asset
+    fn extensions(&self) -> &[&str] {
+        &["ron"]
+    }
+}
+
+// set once config.ron was applied or failed to load, until then the defaults are in place
+#[derive(Resource, Default)]
+pub struct ConfigLoaded(pub bool);
+
+#[derive(Resource)]
+struct GameConfigHandle(Handle<GameConfig>);
+
+fn load_config(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+){
+    commands.insert_resource(GameConfigHandle(asset_server.load(CONFIG_PATH)));
+}
+
+// the file is watched, every successful load replaces the resource.
+// a file that fails to parse or validate is logged by the asset server
+// and the last good config stays in place
+fn apply_config(
+    asset_server: Res<AssetServer>,
+    handle: Res<GameConfigHandle>,
+    configs: Res<Assets<GameConfig>>,
+    mut events: EventReader<AssetEvent<GameConfig>>,
+    mut config: ResMut<GameConfig>,
+    mut config_loaded: ResMut<ConfigLoaded>,
+    mut failed: Local<bool>,
+){
+    for event in events.iter() {
+        let (AssetEvent::Created{handle: loaded} | AssetEvent::Modified{handle: loaded}) = event else { continue };
+        if *loaded != handle.0 {
+            continue;
+        }
+        if let Some(loaded) = configs.get(loaded) {
+            let changes = config.changes(loaded);
+            if changes.is_empty() {
+                info!("{} loaded, nothing changed", CONFIG_PATH);
+            } else {
+                info!("{} loaded, changed {}", CONFIG_PATH, changes.join(", "));
+                *config = loaded.clone();
+            }
+            config_loaded.0 = true;
+        }
+    }
+    let load_failed = asset_server.get_load_state(&handle.0) == LoadState::Failed;
+    if load_failed && !*failed {
+        warn!("{} could not be loaded, keeping the last good config", CONFIG_PATH);
+    }
+    if load_failed && !config_loaded.0 {
+        config_loaded.0 = true;
+    }
+    *failed = load_failed;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(GameConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_value_names_the_field() {
+        let config = GameConfig{stick_dead_zone:1.5, ..default()};
+        let err = config.validate().unwrap_err();
+        assert_eq!(err.field, "stick_dead_zone");
+        assert_eq!(err.to_string(), "invalid config.ron: `stick_dead_zone` must be in 0..1, got 1.5");
+        let config = GameConfig{wave:WaveCurve{max_count:2, ..default()}, ..default()};
+        assert_eq!(config.validate().unwrap_err().field, "wave.max_count");
+        assert_eq!(GameConfig{turn_speed:f32::NAN, ..default()}.validate().unwrap_err().field, "turn_speed");
+    }
+
+    #[test]
+    fn same_config_has_no_changes() {
+        assert!(GameConfig::default().changes(&GameConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn changes_list_old_and_new_values() {
+        let old = GameConfig::default();
+        let mut new = old.clone();
+        new.turn_speed = 2.0;
+        new.seed = Some(7);
+        new.wave.max_count = 20;
+        assert_eq!(old.changes(&new), vec![
+            "turn_speed: 3.1415927 -> 2.0",
+            "seed: None -> Some(7)",
+            "wave.max_count: 12 -> 20",
+        ]);
+    }
+}
@@ -0,0 +1,116 @@
+use bevy::prelude::*;
+
+use crate::actions::{Action, Bindings, BINDINGS_FILE, SLOTS};
+use crate::{screen_text, storage, GameState};
+
+// rebinding screen, opened from the main menu
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Rebinding>()
+            .add_system_set(SystemSet::on_enter(GameState::Controls).with_system(setup_controls))
+            .add_system_set(SystemSet::on_update(GameState::Controls).with_system(controls))
+            .add_system_set(SystemSet::on_exit(GameState::Controls).with_system(crate::despawn_with::<ControlsScreen>));
+    }
+}
+
+#[derive(Component)]
+struct ControlsScreen;
+
+#[derive(Component)]
+struct ControlsText;
+
+// selected action and slot, listening while waiting for the new key
+#[derive(Resource, Default)]
+struct Rebinding{
+    row:usize,
+    slot:usize,
+    listening:bool,
+}
+
+fn setup_controls(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    bindings: Res<Bindings>,
+    mut rebinding: ResMut<Rebinding>,
+){
+    rebinding.listening = false;
+    let (node, mut text) = screen_text(&asset_server, &controls_text(&bindings, &rebinding));
+    text.text.sections[0].style.font_size = 28.0;
+    commands.spawn(node)
+        .with_children(|parent| {
+            parent.spawn(text).insert(ControlsText);
+        })
+        .insert(ControlsScreen);
+}
+
+fn controls_text(bindings:&Bindings, rebinding:&Rebinding) -> String {
+    let mut lines = vec!["Controls".to_string(), String::new()];
+    for (row, action) in Action::ALL.iter().enumerate() {
+        let keys:Vec<String> = (0..SLOTS).map(|slot| {
+            let key = if rebinding.listening && row == rebinding.row && slot == rebinding.slot {
+                "...".to_string()
+            } else {
+                bindings.key(*action, slot).map_or("-".to_string(), |key| format!("{:?}", key))
+            };
+            if row == rebinding.row && slot == rebinding.slot { format!("[{}]", key) } else { key }
+        }).collect();
+        lines.push(format!("{}:  {}", action.name(), keys.join("  ")));
+    }
+    lines.push(String::new());
+    if rebinding.listening {
+        lines.push("Press a key, Escape cancels".to_string());
+    } else {
+        lines.push("Arrows select, Enter rebinds, Delete clears".to_string());
+        lines.push("Escape returns to the menu".to_string());
+    }
+    lines.join("\n")
+}
+
+// menu navigation reads raw keys, only the bindings themselves go through actions
+fn controls(
+    mut state: ResMut<State<GameState>>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut bindings: ResMut<Bindings>,
+    mut rebinding: ResMut<Rebinding>,
+    mut query: Query<&mut Text, With<ControlsText>>,
+){
+    let action = Action::ALL[rebinding.row];
+    if rebinding.listening {
+        let pressed = keyboard_input.get_just_pressed().next().copied();
+        if let Some(key) = pressed {
+            keyboard_input.reset(key);
+            if key != KeyCode::Escape {
+                bindings.bind(action, rebinding.slot, key);
+                storage::save(&storage::settings(), BINDINGS_FILE, &*bindings);
+            }
+            rebinding.listening = false;
+        }
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.reset(KeyCode::Escape);
+        let _ = state.set(GameState::MainMenu);
+        return;
+    } else if keyboard_input.just_pressed(KeyCode::Up) {
+        rebinding.row = (rebinding.row + Action::ALL.len() - 1) % Action::ALL.len();
+    } else if keyboard_input.just_pressed(KeyCode::Down) {
+        rebinding.row = (rebinding.row + 1) % Action::ALL.len();
+    } else if keyboard_input.just_pressed(KeyCode::Left) {
+        rebinding.slot = (rebinding.slot + SLOTS - 1) % SLOTS;
+    } else if keyboard_input.just_pressed(KeyCode::Right) {
+        rebinding.slot = (rebinding.slot + 1) % SLOTS;
+    } else if keyboard_input.just_pressed(KeyCode::Return) {
+        keyboard_input.reset(KeyCode::Return);
+        rebinding.listening = true;
+    } else if keyboard_input.just_pressed(KeyCode::Delete) || keyboard_input.just_pressed(KeyCode::Back) {
+        bindings.clear(action, rebinding.slot);
+        storage::save(&storage::settings(), BINDINGS_FILE, &*bindings);
+    }
+
+    if rebinding.is_changed() || bindings.is_changed() {
+        let value = controls_text(&bindings, &rebinding);
+        for mut text in query.iter_mut() {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use bevy::prelude::*;
+use bevy::time::{FixedTimestep, FixedTimesteps};
+
+use crate::{playing, torus, ArenaBounds, Velocity};
+
+pub const TICK_RATE:f64 = 60.0;
+// simulated seconds per tick, use instead of the frame's delta in FixedUpdate
+pub const TICK:f32 = (1.0 / TICK_RATE) as f32;
+const FIXED_TIMESTEP:&str = "fixed_timestep";
+
+// gameplay runs here at TICK_RATE, zero or more times per frame, before Update
+#[derive(StageLabel)]
+pub struct FixedUpdate;
+
+#[derive(SystemLabel, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum FixedSystem {
+    // puts moving entities back to their simulated pose
+    Restore,
+    Simulate,
+    Record,
+}
+
+// draws moving entities between their last two simulated poses
+#[derive(SystemLabel)]
+pub struct Interpolate;
+
+pub struct FixedUpdatePlugin;
+
+impl Plugin for FixedUpdatePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_stage_before(CoreStage::Update, FixedUpdate, SystemStage::parallel()
+                .with_run_criteria(FixedTimestep::steps_per_second(TICK_RATE).with_label(FIXED_TIMESTEP)))
+            // outside of a run nothing is simulated, the entities stay where they were drawn last
+            .add_system_to_stage(FixedUpdate, restore_transforms.label(FixedSystem::Restore).with_run_criteria(playing))
+            .add_system_to_stage(FixedUpdate, record_transforms.label(FixedSystem::Record).after(FixedSystem::Simulate)
+                .with_run_criteria(playing))
+            .add_system(interpolate_transforms.label(Interpolate).with_run_criteria(playing));
+    }
+}
+
+// translation and rotation after the previous and the latest tick.
+// scale isn't simulated and left alone
+#[derive(Component)]
+pub struct Interpolated{
+    previous:(Vec3, Quat),
+    current:(Vec3, Quat),
+}
+
+fn restore_transforms(
+    mut query: Query<(&mut Transform, &Interpolated)>
+){
+    for (mut transform, interpolated) in query.iter_mut() {
+        (transform.translation, transform.rotation) = interpolated.current;
+    }
+}
+
+// entities start interpolating on the first tick after they were spawned
+fn record_transforms(
+    mut commands: Commands,
+    mut query: Query<(Entity, &Transform, Option<&mut Interpolated>), With<Velocity>>
+){
+    for (entity, transform, interpolated) in query.iter_mut() {
+        let pose = (transform.translation, transform.rotation);
+        match interpolated {
+            Some(mut interpolated) => {
+                interpolated.previous = interpolated.current;
+                interpolated.current = pose;
+            },
+            None => {
+                // a hit this tick may already have despawned it
+                commands.add(move |world: &mut World| {
+                    if let Some(mut entity) = world.get_entity_mut(entity) {
+                        entity.insert(Interpolated{previous:pose, current:pose});
+                    }
+                });
+            },
+        }
+    }
+}
+
+fn interpolate_transforms(
+    fixed_timesteps: Res<FixedTimesteps>,
+    bounds: Res<ArenaBounds>,
+    mut query: Query<(&mut Transform, &Interpolated)>
+){
+    let Some(fixed_timestep) = fixed_timesteps.get(FIXED_TIMESTEP) else { return };
+    let alpha = fixed_timestep.overstep_percentage().clamp(0.0, 1.0) as f32;
+    for (mut transform, interpolated) in query.iter_mut() {
+        let (previous, previous_rotation) = interpolated.previous;
+        let (current, current_rotation) = interpolated.current;
+        // the short way round when the entity wrapped during the tick
+        let previous = torus::nearest(previous, current, bounds.size());
+        transform.translation = torus::wrap(previous.lerp(current, alpha), bounds.min, bounds.size());
+        transform.rotation = previous_rotation.slerp(current_rotation, alpha);
+    }
+}
@@ -0,0 +1,224 @@
+use bevy::prelude::*;
+use bevy::window::ReceivedCharacter;
+use serde::{Deserialize, Serialize};
+
+use crate::touch::tapped;
+use crate::{screen_text, storage, GameState, Score, Wave};
+
+const HIGH_SCORES_FILE:&str = "highscores.ron";
+const MAX_ENTRIES:usize = 10;
+const MAX_NAME:usize = 12;
+
+pub struct HighScoresPlugin;
+
+impl Plugin for HighScoresPlugin {
+    fn build(&self, app: &mut App) {
+        let high_scores = storage::load::<HighScores>(&storage::data(), HIGH_SCORES_FILE).unwrap_or_default();
+        app.insert_resource(high_scores)
+            .init_resource::<PlayerName>()
+            .add_system_set(SystemSet::on_enter(GameState::NameEntry).with_system(setup_name_entry))
+            .add_system_set(SystemSet::on_update(GameState::NameEntry).with_system(name_entry))
+            .add_system_set(SystemSet::on_exit(GameState::NameEntry).with_system(crate::despawn_with::<NameEntryScreen>));
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct HighScore{
+    pub name:String,
+    pub score:i32,
+    pub wave:i32,
+    // yyyy-mm-dd
+    pub date:String,
+}
+
+// best runs first
+#[derive(Resource, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HighScores{
+    pub entries:Vec<HighScore>
+}
+
+impl HighScores {
+    pub fn qualifies(&self, score:i32) -> bool {
+        score > 0 && (self.entries.len() < MAX_ENTRIES || self.entries.iter().any(|entry| score > entry.score))
+    }
+
+    // on equal scores the older entry stays ahead
+    pub fn insert(&mut self, entry:HighScore) {
+        let index = self.entries.iter().position(|other| entry.score > other.score).unwrap_or(self.entries.len());
+        self.entries.insert(index, entry);
+        self.entries.truncate(MAX_ENTRIES);
+    }
+
+    pub fn table(&self) -> String {
+        if self.entries.is_empty() {
+            return "No high scores yet".to_string();
+        }
+        self.entries.iter().enumerate()
+            .map(|(i, entry)| format!("{:>2}. {:<12} {:>6}   wave {:>2}   {}",
+                i + 1, entry.name, entry.score, entry.wave, entry.date))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// the last name entered, offered again on the next qualifying run
+#[derive(Resource, Default)]
+struct PlayerName(String);
+
+#[derive(Component)]
+struct NameEntryScreen;
+
+#[derive(Component)]
+struct NameEntryText;
+
+fn name_entry_text(score:i32, name:&str) -> String {
+    format!("New high score: {}\n\nName: {}_\n\nType your name and press Enter", score, name)
+}
+
+fn setup_name_entry(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    score: Res<Score>,
+    player: Res<PlayerName>,
+    mut characters: ResMut<Events<ReceivedCharacter>>,
+){
+    // keys typed while playing are not part of the name
+    characters.clear();
+    let (node, text) = screen_text(&asset_server, &name_entry_text(score.value, &player.0));
+    commands.spawn(node)
+        .with_children(|parent| {
+            parent.spawn(text).insert(NameEntryText);
+        })
+        .insert(NameEntryScreen);
+}
+
+fn name_entry(
+    mut state: ResMut<State<GameState>>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    touches: Res<Touches>,
+    mouse: Res<Input<MouseButton>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    score: Res<Score>,
+    wave: Res<Wave>,
+    mut player: ResMut<PlayerName>,
+    mut high_scores: ResMut<HighScores>,
+    mut query: Query<&mut Text, With<NameEntryText>>,
+){
+    for character in characters.iter() {
+        let character = character.char;
+        if (character.is_alphanumeric() || character == ' ') && player.0.chars().count() < MAX_NAME {
+            player.0.push(character);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        player.0.pop();
+    }
+    // touch screens have no keyboard, a tap saves the default name
+    if keyboard_input.just_pressed(KeyCode::Return) || tapped(&touches, &mouse) {
+        keyboard_input.reset(KeyCode::Return);
+        let name = match player.0.trim() {
+            "" => "Player".to_string(),
+            name => name.to_string(),
+        };
+        high_scores.insert(HighScore{name, score:score.value, wave:wave.value, date:today()});
+        storage::save(&storage::data(), HIGH_SCORES_FILE, &*high_scores);
+        let _ = state.set(GameState::GameOver);
+        return;
+    }
+    if player.is_changed() {
+        for mut text in query.iter_mut() {
+            text.sections[0].value = name_entry_text(score.value, &player.0);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn today() -> String {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// days since 1970-01-01 to a gregorian date, after Howard Hinnant's days_from_civil
+#[cfg(not(target_arch = "wasm32"))]
+fn civil_from_days(days:i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month as u32, day as u32)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn today() -> String {
+    let date = js_sys::Date::new_0();
+    format!("{:04}-{:02}-{:02}", date.get_full_year(), date.get_month() + 1, date.get_date())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name:&str, score:i32) -> HighScore {
+        HighScore{name:name.to_string(), score, wave:1, date:"2026-10-18".to_string()}
+    }
+
+    // scores 100, 90, .. 10
+    fn full_table() -> HighScores {
+        let mut high_scores = HighScores::default();
+        for i in 0..MAX_ENTRIES as i32 {
+            high_scores.insert(entry(&format!("p{}", i), 100 - 10 * i));
+        }
+        high_scores
+    }
+
+    fn scores(high_scores:&HighScores) -> Vec<i32> {
+        high_scores.entries.iter().map(|entry| entry.score).collect()
+    }
+
+    #[test]
+    fn any_score_qualifies_for_a_table_with_room() {
+        let mut high_scores = HighScores::default();
+        assert!(high_scores.qualifies(1));
+        assert!(!high_scores.qualifies(0));
+        high_scores.insert(entry("a", 50));
+        assert!(high_scores.qualifies(5));
+    }
+
+    #[test]
+    fn full_table_takes_only_better_scores() {
+        let mut high_scores = full_table();
+        assert_eq!(scores(&high_scores), vec![100, 90, 80, 70, 60, 50, 40, 30, 20, 10]);
+        assert!(!high_scores.qualifies(10));
+        assert!(high_scores.qualifies(11));
+        high_scores.insert(entry("new", 55));
+        assert_eq!(scores(&high_scores), vec![100, 90, 80, 70, 60, 55, 50, 40, 30, 20]);
+    }
+
+    #[test]
+    fn equal_score_goes_behind_the_older_entry() {
+        let mut high_scores = full_table();
+        high_scores.insert(entry("new", 50));
+        assert_eq!(high_scores.entries[5].name, "p5");
+        assert_eq!(high_scores.entries[6].name, "new");
+        assert_eq!(high_scores.entries.len(), MAX_ENTRIES);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn days_become_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(20744), (2026, 10, 18));
+        assert_eq!(civil_from_days(47541), (2100, 3, 1));
+    }
+}
@@ -1,13 +1,97 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use bevy::ecs::schedule::ShouldRun;
 use bevy::prelude::*;
 use std::f32::consts::PI;
 use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+mod actions;
+mod asteroid_mesh;
+mod broadphase;
+mod cli;
+mod collision;
+mod config;
+mod controls;
+mod fixed;
+mod highscores;
+mod particles;
+mod replay;
+mod rng;
+mod sound;
+mod storage;
+mod torus;
+mod touch;
+#[cfg(test)]
+mod tests;
+
+use actions::{Action, ActionState, ActionsPlugin, TickActions};
+use broadphase::Broadphase;
+use cli::CommandLine;
+use collision::Collider;
+use config::{ConfigPlugin, GameConfig};
+use controls::ControlsPlugin;
+use fixed::{FixedSystem, FixedUpdate, FixedUpdatePlugin, Interpolate, TICK};
+use highscores::{HighScores, HighScoresPlugin};
+use particles::{ParticleEmitter, ParticlesPlugin};
+use replay::{Replay, ReplayPlugin};
+use rng::GameRng;
+use sound::{SoundEvent, SoundPlugin};
+use touch::{tapped, TouchControlsPlugin};
 
 #[derive(Component)]
 struct Ship;
 
 #[derive(Component)]
 struct Asteroid{
-    divisible:bool
+    size:AsteroidSize,
+    // drawn at spawn, orders asteroids hit at the same moment. entity ids
+    // can't, particles and ghosts take different ones at other frame rates
+    key:u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum AsteroidSize {
+    Large,
+    Medium,
+    Small,
+}
+
+impl AsteroidSize {
+    fn radius(&self, asteroid_size:f32) -> f32 {
+        match self {
+            AsteroidSize::Large => asteroid_size * 2.0,
+            AsteroidSize::Medium => asteroid_size * 1.2,
+            AsteroidSize::Small => asteroid_size * 0.7,
+        }
+    }
+
+    fn speed(&self) -> f32 {
+        match self {
+            AsteroidSize::Large => 1.0,
+            AsteroidSize::Medium => 1.5,
+            AsteroidSize::Small => 2.0,
+        }
+    }
+
+    fn score(&self) -> i32 {
+        match self {
+            AsteroidSize::Large => 20,
+            AsteroidSize::Medium => 50,
+            AsteroidSize::Small => 100,
+        }
+    }
+
+    // size and number of the fragments left behind when destroyed
+    fn split(&self) -> Option<(AsteroidSize, i32)> {
+        match self {
+            AsteroidSize::Large => Some((AsteroidSize::Medium, 2)),
+            AsteroidSize::Medium => Some((AsteroidSize::Small, 2)),
+            AsteroidSize::Small => None,
+        }
+    }
 }
 
 #[derive(Component)]
@@ -19,15 +103,22 @@ struct Scoretext;
 #[derive(Component)]
 struct Shiptext;
 
+#[derive(Component)]
+struct MenuScreen;
+
+#[derive(Component)]
+struct PauseScreen;
+
+#[derive(Component)]
+struct GameOverScreen;
+
 #[derive(Component)]
 struct TurnSpeed{
     value:f32
 }
 
 #[derive(Component)]
-struct Speed{
-    value:f32
-}
+struct Velocity(Vec3);
 
 #[derive(Component)]
 struct Timer{
@@ -35,39 +126,122 @@ struct Timer{
 }
 
 #[derive(Component)]
-struct Shake{
+struct WaveBanner{
+    time:f32
+}
+
+#[derive(Component)]
+struct Invulnerable{
     value:bool,
     default_time:f32,
     time:f32,
 }
 
-#[derive(Resource)]
+// copy of a wrapping entity drawn on the opposite edge while it crosses it,
+// offset is in whole arena sizes
+#[derive(Component)]
+struct Ghost{
+    source:Entity,
+    offset:IVec2
+}
+
+// trauma decays over time, the camera offset grows with its square
+#[derive(Component)]
+struct CameraShake{
+    trauma:f32,
+    base:Vec3,
+}
+
+#[derive(Resource, Default)]
 struct Score {
     value:i32,
     ships:i32
 }
 
-impl Default for Score{
+#[derive(Resource)]
+struct CountLaser{
+    value:i32
+}
+
+// shared handles, created up front so spawning never adds new assets
+#[derive(Resource)]
+struct GameAssets{
+    laser_mesh:Handle<Mesh>,
+    laser_material:Handle<StandardMaterial>,
+    // a few pre-generated meshes per size with their bounding radius
+    asteroid_meshes:HashMap<AsteroidSize, Vec<(Handle<Mesh>, f32)>>,
+    asteroid_materials:Vec<Handle<StandardMaterial>>,
+    // the primitives of ship.gltf, for ghosts that shouldn't instance the whole scene
+    ship_parts:Vec<(Handle<Mesh>, Handle<StandardMaterial>)>,
+}
+
+// visible part of the ground plane in (x, z), everything wraps at its edges
+#[derive(Resource, Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+struct ArenaBounds{
+    min:Vec2,
+    max:Vec2
+}
+
+impl Default for ArenaBounds{
     fn default() -> Self {
         Self {
-            value:0,
-            ships:3,
+            min:Vec2::new(-11.0, -8.0),
+            max:Vec2::new(11.0, 8.0),
         }
     }
 }
 
+impl ArenaBounds{
+    fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+}
+
 #[derive(Resource)]
-struct CountLaser{
+struct Wave{
     value:i32
 }
 
+// collision events, every entity shows up at most once per frame
+struct LaserHitAsteroid{
+    laser:Entity,
+    asteroid:Entity
+}
+
+struct ShipHitAsteroid{
+    ship:Entity
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum GameState {
+    MainMenu,
+    Controls,
+    Playing,
+    Paused,
+    NameEntry,
+    GameOver,
+}
+
+// gameplay in FixedUpdate, which runs outside the state's own stage.
+// stops on the tick the last ship is lost rather than when the state
+// changes at the end of the frame, so a replay ends on the same tick
+fn playing(state: Res<State<GameState>>, score: Res<Score>) -> ShouldRun {
+    if *state.current() == GameState::Playing && score.ships > 0 { ShouldRun::Yes } else { ShouldRun::No }
+}
+
 fn main() {
+    let command_line = CommandLine::parse(std::env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(2);
+    });
+    // a recording only keeps the config a run started with, so config.ron
+    // edits can't apply while recording or replaying
+    let watch_for_changes = command_line.record.is_none() && command_line.replay.is_none();
     App::new()
         //add config resources
+        .insert_resource(command_line)
         .insert_resource(Msaa {samples: 4})
         .insert_resource(ClearColor(Color::MIDNIGHT_BLUE))
-        .insert_resource(Score::default())
-        .insert_resource(CountLaser{value:0})
         //bevy itself
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             window: WindowDescriptor {
@@ -77,29 +251,104 @@ fn main() {
                 ..default()
             },
             ..default()
+        }).set(AssetPlugin {
+            // lets config.ron be edited while the game runs
+            watch_for_changes,
+            ..default()
         }))
+        .add_plugin(ConfigPlugin)
+        .add_plugin(FixedUpdatePlugin)
+        .add_plugin(ActionsPlugin)
+        .add_plugin(ParticlesPlugin)
+        .add_plugin(SoundPlugin)
+        .add_state(GameState::MainMenu)
+        .add_plugin(GamePlugin)
+        .add_plugin(ControlsPlugin)
+        .add_plugin(TouchControlsPlugin)
+        .add_plugin(HighScoresPlugin)
+        .add_plugin(ReplayPlugin)
+        .add_system(camera_shake)
+        .add_system(camera_height)
+        // ghosts follow their source through every state and go with it
+        .add_system(update_ghosts.after(Interpolate))
         // system once
         .add_startup_system(setup)
-        // system frame
-        .add_system(input_ship)
-        .add_system(turn)
-        .add_system(moving)
-        .add_system(timer)
-        .add_system(shake)
-        .add_system(scoreboard)
-        .add_system(collision_ship)
-        .add_system(collision_laser)
+        // main menu
+        .add_system_set(SystemSet::on_enter(GameState::MainMenu).with_system(setup_menu))
+        .add_system_set(SystemSet::on_update(GameState::MainMenu).with_system(menu))
+        .add_system_set(SystemSet::on_exit(GameState::MainMenu).with_system(despawn_with::<MenuScreen>))
+        // playing
+        .add_system_set(SystemSet::on_update(GameState::Playing)
+            .with_system(scoreboard)
+            .with_system(wave_banner)
+            .with_system(pause))
+        // paused
+        .add_system_set(SystemSet::on_enter(GameState::Paused).with_system(setup_pause))
+        .add_system_set(SystemSet::on_update(GameState::Paused).with_system(resume))
+        .add_system_set(SystemSet::on_exit(GameState::Paused).with_system(despawn_with::<PauseScreen>))
+        // game over
+        .add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(setup_game_over))
+        .add_system_set(SystemSet::on_update(GameState::GameOver).with_system(game_over))
+        .add_system_set(SystemSet::on_exit(GameState::GameOver).with_system(despawn_with::<GameOverScreen>))
         .run();
 }
 
-const ASTROID_NUM:i32=3;
-const ASTROID_SIZE:f32=0.5;
+// the run itself, from spawning to the fixed-step simulation and cleanup.
+// needs no window, so tests run it headless
+struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        // reseeded when a run starts
+        app.insert_resource(GameRng::new(0))
+            .insert_resource(Score::default())
+            .insert_resource(CountLaser{value:0})
+            .insert_resource(Wave{value:1})
+            .insert_resource(ArenaBounds::default())
+            .insert_resource(Broadphase::new(ArenaBounds::default().min, ArenaBounds::default().max, BROADPHASE_CELL))
+            .add_event::<LaserHitAsteroid>()
+            .add_event::<ShipHitAsteroid>()
+            .add_system(arena_bounds)
+            .add_system(build_game_assets)
+            .add_system(retune)
+            .add_system_set(SystemSet::on_enter(GameState::Playing)
+                .with_system(reset_game.after(arena_bounds))
+                .with_system(setup_game.after(reset_game)))
+            .add_system_set_to_stage(FixedUpdate, SystemSet::new()
+                .label(FixedSystem::Simulate)
+                .after(FixedSystem::Restore)
+                .with_run_criteria(playing)
+                // one after another, systems that touch the same entities or
+                // draw from GameRng would otherwise take turns in any order
+                .with_system(input_ship)
+                .with_system(hyperspace.after(input_ship))
+                .with_system(turn.after(hyperspace))
+                .with_system(moving.after(turn))
+                .with_system(timer.after(moving))
+                .with_system(invulnerability.after(timer))
+                .with_system(update_broadphase.after(invulnerability))
+                .with_system(detect_ship_hits.after(update_broadphase))
+                .with_system(detect_laser_hits.after(detect_ship_hits))
+                .with_system(score_hits.after(detect_laser_hits))
+                .with_system(split_asteroids.after(score_hits))
+                .with_system(despawn_hits.after(split_asteroids))
+                .with_system(hit_effects.after(despawn_hits))
+                .with_system(ship_hit.after(hit_effects))
+                .with_system(next_wave.after(ship_hit)))
+            .add_system_set(SystemSet::on_exit(GameState::Playing)
+                .with_system(despawn_with::<Ship>)
+                .with_system(despawn_with::<Scoretext>)
+                .with_system(despawn_with::<Shiptext>)
+                .with_system(despawn_with::<WaveBanner>))
+            .add_system_set(SystemSet::on_exit(GameState::GameOver)
+                .with_system(despawn_with::<Asteroid>)
+                .with_system(despawn_with::<Laser>));
+    }
+}
 
 fn setup(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    config: Res<GameConfig>,
 ){
     // light
     commands.spawn(PointLightBundle{
@@ -117,15 +366,100 @@ fn setup(
         brightness: 0.02,
     });
     //camera
+   let transform = Transform::from_xyz(0.0,config.camera_height,0.5).looking_at(Vec3::new(0.,0.,0.), Vec3::Y);
    commands.spawn(Camera3dBundle{ //0.8
-        transform: Transform::from_xyz(0.0,20.0,0.5).looking_at(Vec3::new(0.,0.,0.), Vec3::Y),
+        transform,
         ..Default::default()
     })
    .insert(UiCameraConfig {
        show_ui: true,
-       ..default()
-   });
+   })
+   .insert(CameraShake{trauma:0.0, base:transform.translation});
+}
+
+const ASTEROID_VARIANTS:u64 = 4;
+// ship.gltf has one mesh of four primitives, each with its own material,
+// on a node turned a quarter round the x axis
+const SHIP_PARTS:usize = 4;
+const SHIP_MODEL_ROTATION:Quat = Quat::from_xyzw(std::f32::consts::FRAC_1_SQRT_2, 0.0, 0.0, std::f32::consts::FRAC_1_SQRT_2);
+const ASTEROID_PALETTE:usize = 8;
+
+// rebuilt whenever the config changes, rocks already in play keep their old meshes
+fn build_game_assets(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    config: Res<GameConfig>,
+    mut rng: ResMut<GameRng>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+){
+    if !config.is_changed() {
+        return;
+    }
+    let mut asteroid_meshes = HashMap::default();
+    for (tier, size) in [AsteroidSize::Large, AsteroidSize::Medium, AsteroidSize::Small].into_iter().enumerate() {
+        let list = (0..ASTEROID_VARIANTS).map(|i| {
+            let seed = tier as u64 * ASTEROID_VARIANTS + i;
+            let (mesh, radius) = asteroid_mesh::asteroid_mesh(seed, size.radius(config.asteroid_size) * 0.75, config.asteroid_roughness);
+            (meshes.add(mesh), radius)
+        }).collect();
+        asteroid_meshes.insert(size, list);
+    }
+    let asteroid_materials = (0..ASTEROID_PALETTE)
+        .map(|_| materials.add(asteroid_color(&mut rng.effects).into()))
+        .collect();
+
+    commands.insert_resource(GameAssets{
+        laser_mesh: meshes.add(Mesh::from(shape::Box::new(0.1, 0.1, 1.6))),
+        laser_material: materials.add(StandardMaterial {
+            base_color: Color::LIME_GREEN,
+            emissive: Color::LIME_GREEN,
+            ..Default::default()
+        }),
+        asteroid_meshes,
+        asteroid_materials,
+        ship_parts: (0..SHIP_PARTS).map(|i| (
+            asset_server.load(format!("models/ship.gltf#Mesh0/Primitive{}", i)),
+            asset_server.load(format!("models/ship.gltf#Material{}", i)),
+        )).collect(),
+    });
+}
 
+// carries config edits over to what is already in play,
+// everything else reads the config when it is used
+fn retune(
+    config: Res<GameConfig>,
+    wave: Res<Wave>,
+    mut last: Local<GameConfig>,
+    mut query_ship: Query<&mut Invulnerable, With<Ship>>,
+    mut query_astro: Query<(&mut Transform, &mut Velocity), With<Asteroid>>
+){
+    if !config.is_changed() {
+        return;
+    }
+    for mut invulnerable in query_ship.iter_mut() {
+        invulnerable.default_time = config.invulnerable_time;
+    }
+    let scale = config.asteroid_size / last.asteroid_size;
+    let speed = config.wave.speed(wave.value) / last.wave.speed(wave.value);
+    if scale != 1.0 || speed != 1.0 {
+        for (mut transform, mut velocity) in query_astro.iter_mut() {
+            transform.scale *= scale;
+            velocity.0 *= speed;
+        }
+    }
+    *last = config.clone();
+}
+
+fn setup_game(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    game_assets: Res<GameAssets>,
+    mut wave: ResMut<Wave>,
+    mut rng: ResMut<GameRng>,
+    config: Res<GameConfig>,
+    bounds: Res<ArenaBounds>,
+){
     // scoreboard
     commands.spawn(TextBundle {
         text: Text::from_section(
@@ -184,125 +518,331 @@ fn setup(
             })
     .insert(Ship)
     .insert(TurnSpeed{value:0.0})
-    .insert(Shake{value:false, default_time:2.0, time:0.0})
-    .insert(Speed{value:0.0});
+    .insert(Invulnerable{value:false, default_time:config.invulnerable_time, time:0.0})
+    .insert(ship_collider())
+    .insert(Velocity(Vec3::ZERO));
 
     //Asteroids
-    let mut rng = rand::thread_rng();
-    for _ in 0..ASTROID_NUM {
-        //find position
+    wave.value = 1;
+    spawn_asteroids(&mut commands, &mut rng.gameplay, &game_assets, &bounds, config.asteroid_size,
+        config.wave.count(wave.value), config.wave.speed(wave.value), ship_position);
+    spawn_wave_banner(&mut commands, &asset_server, wave.value);
+}
+
+const SPAWN_TRIES:usize = 100;
+
+fn spawn_asteroids(
+    commands: &mut Commands,
+    rng: &mut ChaCha8Rng,
+    game_assets: &GameAssets,
+    bounds: &ArenaBounds,
+    asteroid_size: f32,
+    count: i32,
+    speed: f32,
+    ship_position: Vec3,
+){
+    let size = AsteroidSize::Large;
+    for _ in 0..count {
+        //find position, an arena too small to keep clear of the ship gets the last try
         let mut pos = Vec3::new(0.0,0.0,0.0);
-        let mut found = false;
-        while !found {
-            let x = rng.gen_range(BOUND_MIN_X..BOUND_MAX_X);
-            let z = rng.gen_range(BOUND_MIN_Z..BOUND_MAX_Z);
+        for _ in 0..SPAWN_TRIES {
+            let x = rng.gen_range(bounds.min.x..bounds.max.x);
+            let z = rng.gen_range(bounds.min.y..bounds.max.y);
             pos = Vec3::new(x, 0.0, z);
-            found = pos.distance(ship_position) > 2.0 * ASTROID_SIZE;
-        }
-        //create parts
-        let mut children_list:Vec<Entity> = Vec::new();
-        for _ in 0..4{
-            let child_position = Vec3::new(rng.gen_range(0.0..ASTROID_SIZE),
-                rng.gen_range(0.0..ASTROID_SIZE),
-                rng.gen_range(0.0..ASTROID_SIZE));
-            let entity = commands.spawn(PbrBundle {
-               mesh: meshes.add(Mesh::from(shape::Icosphere { radius: ASTROID_SIZE, subdivisions: 32, })),
-               material: materials.add(asteroid_color().into()),
-               transform: Transform::from_translation(child_position),
-                ..Default::default()
-            }).id();
-            children_list.push(entity);
+            // measured across the edges, the ship may sit right at one
+            if torus::delta(ship_position, pos, bounds.size()).length() > 2.0 * size.radius(asteroid_size) {
+                break;
+            }
         }
         //direction
-        let direction = rng.gen_range(0.0..2.0);
+        let rotation = Quat::from_rotation_y(rng.gen_range(0.0..2.0)*PI);
+        spawn_asteroid(commands, rng, game_assets, size, pos, rotation, speed * size.speed());
+    }
+}
 
-        commands.spawn(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Icosphere { radius: ASTROID_SIZE, subdivisions: 32, })),
-            material: materials.add(asteroid_color().into()),
-            transform: Transform{
-                translation: pos,
-                rotation: Quat::from_rotation_y(direction*PI),
-                scale: Vec3::new(1.0,1.0,1.0)
-            },
-            ..Default::default()
+fn spawn_asteroid(
+    commands: &mut Commands,
+    rng: &mut ChaCha8Rng,
+    game_assets: &GameAssets,
+    size: AsteroidSize,
+    position: Vec3,
+    rotation: Quat,
+    speed: f32,
+){
+    let variants = &game_assets.asteroid_meshes[&size];
+    let (mesh, radius) = variants[rng.gen_range(0..variants.len())].clone();
+    let palette = &game_assets.asteroid_materials;
+    let material = palette[rng.gen_range(0..palette.len())].clone();
+    let key = rng.gen();
+
+    commands.spawn(PbrBundle {
+        mesh,
+        material,
+        transform: Transform{
+            translation: position,
+            rotation,
+            scale: Vec3::new(1.0,1.0,1.0)
+        },
+        ..Default::default()
+    })
+    .insert(Velocity(rotation * Vec3::Z * speed))
+    .insert(Asteroid{size, key})
+    .insert(Collider::Sphere{radius});
+}
+
+fn spawn_wave_banner(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    wave: i32,
+){
+    let (node, text) = screen_text(asset_server, &format!("Wave {}", wave));
+    commands.spawn(node)
+        .with_children(|parent| {
+            parent.spawn(text);
         })
-        .push_children(&children_list)
-        .insert(Speed{value:1.0})
-        .insert(Asteroid{divisible:true});
+        .insert(WaveBanner{time:WAVE_BANNER_TIME});
+}
+
+const WAVE_BANNER_TIME:f32 = 2.0;
+
+fn next_wave(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    game_assets: Res<GameAssets>,
+    mut wave: ResMut<Wave>,
+    mut rng: ResMut<GameRng>,
+    config: Res<GameConfig>,
+    bounds: Res<ArenaBounds>,
+    query_ship: Query<&Transform, With<Ship>>,
+    query_astro: Query<(), With<Asteroid>>
+){
+    if query_astro.is_empty() {
+        wave.value += 1;
+        let ship_position = query_ship.single().translation;
+        spawn_asteroids(&mut commands, &mut rng.gameplay, &game_assets, &bounds, config.asteroid_size,
+            config.wave.count(wave.value), config.wave.speed(wave.value), ship_position);
+        spawn_wave_banner(&mut commands, &asset_server, wave.value);
+    }
+}
+
+fn wave_banner(
+    mut commands: Commands,
+    time:Res<Time>,
+    mut query: Query<(Entity, &mut WaveBanner)>
+){
+    for (entity, mut banner) in query.iter_mut(){
+        banner.time -= time.delta_seconds();
+        if banner.time < 0.0 {
+            commands.entity(entity).despawn_recursive();
+        }
     }
 }
 
-fn asteroid_color()->Color {
-    let mut rng = rand::thread_rng();
+fn asteroid_color(rng: &mut ChaCha8Rng)->Color {
     Color::from([rng.gen_range(0.8..1.0),
         rng.gen_range(0.8..1.0),
         rng.gen_range(0.8..1.0)])
 }
 
-const TURN_SPEED:f32= PI;
-const SHIP_THRUST:f32= 1.0;
-const FRICTION:f32=0.8;
-const MAX_LASER:i32=10;
+fn screen_text(asset_server: &AssetServer, text: &str) -> (NodeBundle, TextBundle) {
+    (NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            position_type: PositionType::Absolute,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        ..Default::default()
+    },
+    TextBundle::from_section(
+        text,
+        TextStyle {
+            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+            font_size: 40.0,
+            color: Color::rgb(0.5, 0.5, 1.0),
+        }
+    )
+    .with_text_alignment(TextAlignment::CENTER))
+}
+
+fn setup_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+){
+    let (node, text) = screen_text(&asset_server, "bevyastro\n\nPress Enter or tap to start\nPress C for controls");
+    commands.spawn(node)
+        .with_children(|parent| {
+            parent.spawn(text);
+        })
+        .insert(MenuScreen);
+}
+
+fn menu(
+    mut state: ResMut<State<GameState>>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    touches: Res<Touches>,
+    mouse: Res<Input<MouseButton>>,
+){
+    if keyboard_input.just_pressed(KeyCode::Return) || tapped(&touches, &mouse) {
+        keyboard_input.reset(KeyCode::Return);
+        state.set(GameState::Playing).unwrap();
+    } else if keyboard_input.just_pressed(KeyCode::C) {
+        keyboard_input.reset(KeyCode::C);
+        let _ = state.set(GameState::Controls);
+    }
+}
+
+fn pause(
+    mut state: ResMut<State<GameState>>,
+    mut actions: ResMut<ActionState>,
+){
+    if actions.consume(Action::Pause) {
+        let _ = state.push(GameState::Paused);
+    }
+}
+
+fn setup_pause(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+){
+    let (node, text) = screen_text(&asset_server, "Paused\n\nPause again to continue");
+    commands.spawn(node)
+        .with_children(|parent| {
+            parent.spawn(text);
+        })
+        .insert(PauseScreen);
+}
+
+fn resume(
+    mut state: ResMut<State<GameState>>,
+    mut actions: ResMut<ActionState>,
+){
+    if actions.consume(Action::Pause) {
+        state.pop().unwrap();
+    }
+}
+
+fn setup_game_over(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    score: Res<Score>,
+    high_scores: Res<HighScores>,
+){
+    let message = format!("Game Over\n\nScore: {}\n\n{}\n\nPress Enter or tap to restart",
+        score.value, high_scores.table());
+    let (node, mut text) = screen_text(&asset_server, &message);
+    text.text.sections[0].style.font_size = 24.0;
+    commands.spawn(node)
+        .with_children(|parent| {
+            parent.spawn(text);
+        })
+        .insert(GameOverScreen);
+}
+
+fn game_over(
+    mut state: ResMut<State<GameState>>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    touches: Res<Touches>,
+    mouse: Res<Input<MouseButton>>,
+){
+    if keyboard_input.just_pressed(KeyCode::Return) || tapped(&touches, &mouse) {
+        keyboard_input.reset(KeyCode::Return);
+        state.set(GameState::Playing).unwrap();
+    }
+}
+
+fn reset_game(
+    config: Res<GameConfig>,
+    command_line: Res<CommandLine>,
+    replay: Option<Res<Replay>>,
+    mut rng: ResMut<GameRng>,
+    mut score: ResMut<Score>,
+    mut count_laser: ResMut<CountLaser>,
+){
+    // the same seed and inputs replay the same run
+    let seed = replay.map(|replay| replay.seed()).or(command_line.seed).or(config.seed);
+    *rng = GameRng::new(seed.unwrap_or_else(rand::random));
+    info!("run seed {}", rng.seed);
+    *score = Score{value:0, ships:config.ships};
+    count_laser.value = 0;
+}
+
+fn despawn_with<T: Component>(
+    mut commands: Commands,
+    query: Query<Entity, With<T>>
+){
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
 
 fn input_ship(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    game_assets: Res<GameAssets>,
+    config: Res<GameConfig>,
     mut count_laser: ResMut<CountLaser>,
-    time:Res<Time>,
-    keyboard_input:Res<Input<KeyCode>>,
-    mut query: Query<(&mut TurnSpeed,&mut Speed, &Transform, &Shake), With<Ship>>
-){
-    let (mut turnspeed,mut speed,transform,shake) = query.single_mut();
-    if !shake.value {
-        turnspeed.value = if keyboard_input.pressed(KeyCode::Left) {
-            TURN_SPEED
-        } else if keyboard_input.pressed(KeyCode::Right) {
-            -TURN_SPEED
-        } else {
-            0.0
-        };
-        speed.value = if keyboard_input.pressed(KeyCode::Up) {
-            speed.value + SHIP_THRUST * time.delta_seconds()
-        } else {
-            if speed.value > 0.0 {
-                speed.value - FRICTION * time.delta_seconds()
-            } else {
-                0.0
-            }
-        };
-        if keyboard_input.just_pressed(KeyCode::Space) {
-            if count_laser.value <= MAX_LASER {
-                count_laser.value += 1;
-                commands.spawn(PbrBundle {
-                    mesh: meshes.add(Mesh::from(shape::Box::new(0.1, 0.1, 1.6))),
-                    material: materials.add(StandardMaterial {
-                        base_color: Color::LIME_GREEN,
-                        emissive: Color::LIME_GREEN,
-                        ..Default::default()
-                    }),
-                    transform: Transform {
-                        translation: transform.translation,
-                        rotation: transform.rotation,
-                        scale: Vec3::new(1.0, 1.0, 1.0)
-                    },
-                    ..Default::default()
-                })
-                    .insert(Timer { value: 1.0 })
-                    .insert(Speed { value: 8.0 })
-                    .insert(Laser);
-            }
-        }
+    mut sound_events: EventWriter<SoundEvent>,
+    actions:Res<TickActions>,
+    mut query: Query<(&mut TurnSpeed,&mut Velocity, &Transform), With<Ship>>
+){
+    let (mut turnspeed,mut velocity,transform) = query.single_mut();
+    // analog input turns and thrusts in proportion, keys go all the way
+    turnspeed.value = (actions.value(Action::RotateLeft) - actions.value(Action::RotateRight)) * config.turn_speed;
+    // thrust accelerates along the heading, the ship keeps drifting while it turns
+    let heading = transform.back();
+    velocity.0 = if actions.pressed(Action::Thrust) {
+        sound_events.send(SoundEvent::Thrust);
+        velocity.0 + heading * config.ship_thrust * actions.value(Action::Thrust) * TICK
+    } else {
+        let speed = (velocity.0.length() - config.friction * TICK).max(0.0);
+        velocity.0.normalize_or_zero() * speed
+    };
+    velocity.0 = velocity.0.clamp_length_max(config.ship_max_speed);
+    if actions.just_pressed(Action::Fire) && count_laser.value < config.max_laser {
+        count_laser.value += 1;
+        sound_events.send(SoundEvent::Laser);
+        commands.spawn(PbrBundle {
+            mesh: game_assets.laser_mesh.clone(),
+            material: game_assets.laser_material.clone(),
+            transform: Transform {
+                translation: transform.translation,
+                rotation: transform.rotation,
+                scale: Vec3::new(1.0, 1.0, 1.0)
+            },
+            ..Default::default()
+        })
+            .insert(Timer { value: config.laser_lifetime })
+            .insert(Velocity(heading * config.laser_speed + velocity.0))
+            .insert(Collider::Capsule{half_length:0.75, radius:0.05})
+            .insert(Laser);
+    }
+}
+
+// jumps the ship to a random spot, it may well land next to a rock
+fn hyperspace(
+    actions:Res<TickActions>,
+    mut rng: ResMut<GameRng>,
+    bounds: Res<ArenaBounds>,
+    mut query: Query<(&mut Transform, &mut Velocity), With<Ship>>
+){
+    if !actions.just_pressed(Action::Hyperspace) {
+        return;
+    }
+    for (mut transform, mut velocity) in query.iter_mut() {
+        transform.translation = Vec3::new(rng.gameplay.gen_range(bounds.min.x..bounds.max.x),
+                                          0.0,
+                                          rng.gameplay.gen_range(bounds.min.y..bounds.max.y));
+        velocity.0 = Vec3::ZERO;
     }
 }
 
 fn turn(
-    time:Res<Time>,
     mut query: Query<(&mut Transform, &mut TurnSpeed)>
 ){
     for (mut transform, turnspeed) in query.iter_mut() {
         if turnspeed.value != 0.0 {
-            let rotation_change = Quat::from_rotation_y(turnspeed.value*time.delta_seconds());
+            let rotation_change = Quat::from_rotation_y(turnspeed.value*TICK);
             transform.rotate(rotation_change);
         }
     }
@@ -310,78 +850,206 @@ fn turn(
 
 fn timer(
     mut commands: Commands,
-    time:Res<Time>,
     mut count_laser: ResMut<CountLaser>,
-    mut query: Query<(Entity, &mut Timer)>
+    mut query: Query<(Entity, &mut Timer, &Transform)>
 ){
-    for (entity, mut timer) in query.iter_mut(){
-        timer.value -= time.delta_seconds();
+    for (entity, mut timer, transform) in query.iter_mut(){
+        timer.value -= TICK;
         if timer.value < 0.0 {
+            commands.spawn((ParticleEmitter::laser(), Transform::from_translation(transform.translation)));
             commands.entity(entity).despawn_recursive();
             count_laser.value -= 1;
         }
     }
 }
 
-const COLLISION_DISTANCE:f32 = ASTROID_SIZE * 2.0;
+const BROADPHASE_CELL:f32 = 2.0;
 
-fn collision_laser(
-    mut commands: Commands,
-    mut score: ResMut<Score>,
-    mut count_laser: ResMut<CountLaser>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    query_laser: Query<(Entity, &Transform),With<Laser>>,
-    query_astro: Query<(Entity, &Transform, &Asteroid)>
-){
-    for (laser, laser_transform) in query_laser.iter() {
-        for (asteroid_entity, asteroid_transform, asteroid) in query_astro.iter() {
-            if laser_transform.translation.distance(asteroid_transform.translation) < COLLISION_DISTANCE {
-                commands.entity(laser).despawn_recursive();
-                count_laser.value -=1;
-                if asteroid.divisible {
-                    let mut rng = rand::thread_rng();
-                    for _ in 0..5{
-                        commands.spawn(PbrBundle {
-                            mesh: meshes.add(Mesh::from(shape::Icosphere { radius: ASTROID_SIZE, subdivisions: 32, })),
-                            material: materials.add(asteroid_color().into()),
-                            transform: Transform {
-                                translation: asteroid_transform.translation.clone(),
-                                rotation: Quat::from_rotation_y(rng.gen_range(0.0..2.0)*PI),
-                                ..Default::default()
-                            },
-                            ..Default::default()
-                        })
-                        .insert(Speed{value:1.0})
-                        .insert(Asteroid{divisible:false});
-                    }
+fn update_broadphase(
+    bounds: Res<ArenaBounds>,
+    mut broadphase: ResMut<Broadphase>,
+    query: Query<(Entity, &Transform, &Collider)>
+){
+    if bounds.is_changed() {
+        *broadphase = Broadphase::new(bounds.min, bounds.max, BROADPHASE_CELL);
+    }
+    broadphase.clear();
+    for (entity, transform, collider) in query.iter() {
+        broadphase.insert(entity, transform.translation, collider.bounding_radius() * transform.scale.max_element());
+    }
+}
+
+// the copy of `transform` on the wrapping arena closest to `reference`
+fn near_transform(transform:&Transform, reference:Vec3, bounds:&ArenaBounds) -> Transform {
+    Transform {
+        translation: torus::nearest(transform.translation, reference, bounds.size()),
+        ..*transform
+    }
+}
+
+// lasers are swept from their previous position relative to each asteroid,
+// so fast lasers can't tunnel through small fragments at low frame rates
+fn detect_laser_hits(
+    bounds: Res<ArenaBounds>,
+    broadphase: Res<Broadphase>,
+    mut candidates: Local<Vec<Entity>>,
+    mut hit_asteroids: Local<Vec<Entity>>,
+    mut hits: EventWriter<LaserHitAsteroid>,
+    query_laser: Query<(Entity, &Transform, &Collider, &Velocity, &Timer),With<Laser>>,
+    query_astro: Query<(Entity, &Transform, &Collider, &Velocity, &Asteroid)>
+){
+    let delta = TICK;
+    hit_asteroids.clear();
+    for (laser, laser_transform, laser_collider, laser_velocity, timer) in query_laser.iter() {
+        // expired lasers are already despawned by the timer
+        if timer.value < 0.0 {
+            continue;
+        }
+        let laser_motion = laser_velocity.0 * delta;
+        broadphase.query(laser_transform.translation - laser_motion * 0.5,
+            laser_collider.bounding_radius() * laser_transform.scale.max_element() + laser_motion.length(),
+            &mut candidates);
+        // only the asteroid touched first along the sweep is hit
+        let mut first_hit:Option<(f32, u64, Entity)> = None;
+        for (asteroid, asteroid_transform, asteroid_collider, asteroid_velocity, Asteroid{key, ..}) in query_astro.iter_many(candidates.iter()) {
+            if hit_asteroids.contains(&asteroid) {
+                continue;
+            }
+            let motion = laser_motion - asteroid_velocity.0 * delta;
+            let asteroid_transform = near_transform(asteroid_transform, laser_transform.translation, &bounds);
+            if let Some(t) = collision::swept_overlap(laser_collider, laser_transform, motion, asteroid_collider, &asteroid_transform) {
+                if first_hit.is_none_or(|(first, first_key, _)| (t, *key) < (first, first_key)) {
+                    first_hit = Some((t, *key, asteroid));
                 }
-                score.value += 10;
-                commands.entity(asteroid_entity).despawn_recursive();
             }
         }
+        if let Some((_, _, asteroid)) = first_hit {
+            hit_asteroids.push(asteroid);
+            hits.send(LaserHitAsteroid{laser, asteroid});
+        }
+    }
+}
+
+fn score_hits(
+    mut score: ResMut<Score>,
+    mut hits: EventReader<LaserHitAsteroid>,
+    query_astro: Query<&Asteroid>
+){
+    for hit in hits.iter() {
+        if let Ok(asteroid) = query_astro.get(hit.asteroid) {
+            score.value += asteroid.size.score();
+        }
+    }
+}
+
+fn split_asteroids(
+    mut commands: Commands,
+    mut rng: ResMut<GameRng>,
+    game_assets: Res<GameAssets>,
+    mut hits: EventReader<LaserHitAsteroid>,
+    query_astro: Query<(&Transform, &Velocity, &Asteroid)>
+){
+    for hit in hits.iter() {
+        let Ok((transform, velocity, asteroid)) = query_astro.get(hit.asteroid) else { continue };
+        if let Some((fragment_size, count)) = asteroid.size.split() {
+            // fragments keep the wave speed of their parent
+            let speed = velocity.0.length() / asteroid.size.speed() * fragment_size.speed();
+            for _ in 0..count{
+                let rotation = Quat::from_rotation_y(rng.gameplay.gen_range(0.0..2.0)*PI);
+                spawn_asteroid(&mut commands, &mut rng.gameplay, &game_assets,
+                    fragment_size, transform.translation, rotation, speed);
+            }
+        }
+    }
+}
 
+fn despawn_hits(
+    mut commands: Commands,
+    mut count_laser: ResMut<CountLaser>,
+    mut hits: EventReader<LaserHitAsteroid>,
+){
+    for hit in hits.iter() {
+        commands.entity(hit.laser).despawn_recursive();
+        count_laser.value -=1;
+        commands.entity(hit.asteroid).despawn_recursive();
     }
 }
 
+fn hit_effects(
+    mut commands: Commands,
+    mut sound_events: EventWriter<SoundEvent>,
+    mut laser_hits: EventReader<LaserHitAsteroid>,
+    mut ship_hits: EventReader<ShipHitAsteroid>,
+    mut query_camera: Query<&mut CameraShake>,
+    query_astro: Query<(&Transform, &Collider, &Asteroid)>,
+    query_ship: Query<&Transform, With<Ship>>
+){
+    for hit in laser_hits.iter() {
+        if let Ok((transform, collider, asteroid)) = query_astro.get(hit.asteroid) {
+            sound_events.send(SoundEvent::Explosion(asteroid.size));
+            commands.spawn((ParticleEmitter::asteroid(collider.bounding_radius()), Transform::from_translation(transform.translation)));
+        }
+    }
+    for hit in ship_hits.iter() {
+        if let Ok(transform) = query_ship.get(hit.ship) {
+            sound_events.send(SoundEvent::ShipHit);
+            commands.spawn((ParticleEmitter::ship(), Transform::from_translation(transform.translation)));
+            for mut camera_shake in query_camera.iter_mut() {
+                camera_shake.trauma = 1.0;
+            }
+        }
+    }
+}
 
-const COLLISION_DISTANCE_SHIP:f32 = ASTROID_SIZE * 2.0;
+// body plus both wings of ship.gltf
+fn ship_collider() -> Collider {
+    Collider::Compound(vec![
+        (Vec3::ZERO, Collider::Sphere{radius:0.6}),
+        (Vec3::new(0.9, 0.0, 0.1), Collider::Sphere{radius:0.45}),
+        (Vec3::new(-0.9, 0.0, 0.1), Collider::Sphere{radius:0.45}),
+    ])
+}
+
+fn detect_ship_hits(
+    bounds: Res<ArenaBounds>,
+    broadphase: Res<Broadphase>,
+    mut candidates: Local<Vec<Entity>>,
+    mut hits: EventWriter<ShipHitAsteroid>,
+    query_ship: Query<(Entity, &Transform, &Collider, &Invulnerable),With<Ship>>,
+    query_astro: Query<(&Transform, &Collider), With<Asteroid>>
+){
+    for (ship, ship_transform, ship_collider, invulnerable) in query_ship.iter() {
+        if invulnerable.value {
+            continue;
+        }
+        broadphase.query(ship_transform.translation,
+            ship_collider.bounding_radius() * ship_transform.scale.max_element(), &mut candidates);
+        if query_astro.iter_many(candidates.iter()).any(|(asteroid_transform, asteroid_collider)| {
+            collision::overlaps(ship_collider, ship_transform,
+                asteroid_collider, &near_transform(asteroid_transform, ship_transform.translation, &bounds))
+        }) {
+            hits.send(ShipHitAsteroid{ship});
+        }
+    }
+}
 
-fn collision_ship(
+fn ship_hit(
+    mut state: ResMut<State<GameState>>,
     mut score: ResMut<Score>,
-    mut query_ship: Query<(&Transform, &mut Shake),With<Ship>>,
-    query_astro: Query<&Transform, With<Asteroid>>
-){
-    let (ship_transform, mut ship_shake) = query_ship.single_mut();
-    if !ship_shake.value {
-        for asteroid_transform in query_astro.iter() {
-            if ship_transform.translation.distance(asteroid_transform.translation) < COLLISION_DISTANCE_SHIP {
-                score.ships -= 1;
-                ship_shake.value = true;
-                ship_shake.time = ship_shake.default_time;
+    high_scores: Res<HighScores>,
+    mut hits: EventReader<ShipHitAsteroid>,
+    mut query_ship: Query<&mut Invulnerable, With<Ship>>
+){
+    for hit in hits.iter() {
+        if let Ok(mut invulnerable) = query_ship.get_mut(hit.ship) {
+            score.ships -= 1;
+            invulnerable.value = true;
+            invulnerable.time = invulnerable.default_time;
+            if score.ships <= 0 {
+                let next = if high_scores.qualifies(score.value) { GameState::NameEntry } else { GameState::GameOver };
+                let _ = state.set(next);
             }
         }
-
     }
 }
 
@@ -397,47 +1065,183 @@ fn scoreboard(
     ship_text.sections[0].value = format!("Ship: {}", score.ships);
 }
 
-const BOUND_MAX_X:f32 = 11.0;
-const BOUND_MIN_X:f32 = -11.0;
-const BOUND_MAX_Z:f32 = 8.0;
-const BOUND_MIN_Z:f32 = -8.0;
 
 fn moving(
-    time:Res<Time>,
-    mut query: Query<(&mut Transform, &mut Speed)>,
+    bounds: Res<ArenaBounds>,
+    mut query: Query<(&mut Transform, &Velocity)>,
 ){
-    for (mut transform, speed) in query.iter_mut() {
-        if speed.value != 0.0 {
-            let translation_change = transform.forward() * speed.value * time.delta_seconds();
-            transform.translation -= translation_change;
+    for (mut transform, velocity) in query.iter_mut() {
+        if velocity.0 != Vec3::ZERO {
+            transform.translation += velocity.0 * TICK;
 
-            if transform.translation.x < BOUND_MIN_X { transform.translation.x = BOUND_MAX_X}
-            else if transform.translation.x > BOUND_MAX_X {transform.translation.x = BOUND_MIN_X};
-            if transform.translation.z < BOUND_MIN_Z { transform.translation.z = BOUND_MAX_Z}
-            else if transform.translation.z > BOUND_MAX_Z {transform.translation.z = BOUND_MIN_Z};
+            transform.translation = torus::wrap(transform.translation, bounds.min, bounds.size());
         }
     }
 }
 
-const SHAKE_VARIANCE:f32=0.5;
+// inner rectangle of the camera frustum's footprint on the y = 0 plane
+fn visible_bounds(camera:&Transform, fov:f32, aspect_ratio:f32) -> ArenaBounds {
+    let half_height = (fov * 0.5).tan();
+    let mut xs = Vec::new();
+    let mut zs = Vec::new();
+    for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+        let direction = camera.rotation * Vec3::new(x * half_height * aspect_ratio, y * half_height, -1.0);
+        let point = camera.translation - direction * (camera.translation.y / direction.y);
+        xs.push(point.x);
+        zs.push(point.z);
+    }
+    xs.sort_by(f32::total_cmp);
+    zs.sort_by(f32::total_cmp);
+    // the second smallest and second largest corner keep the rectangle inside a trapezoid
+    ArenaBounds {
+        min:Vec2::new(xs[1], zs[1]),
+        max:Vec2::new(xs[2], zs[2]),
+    }
+}
 
-fn shake(
+fn arena_bounds(
+    windows: Res<Windows>,
+    replay: Option<Res<Replay>>,
+    mut bounds: ResMut<ArenaBounds>,
+    query_camera: Query<(&Transform, &Projection, Option<&CameraShake>), With<Camera3d>>
+){
+    // a replay plays in the recorded bounds, whatever the window
+    if replay.is_some() {
+        return;
+    }
+    let Some(window) = windows.get_primary() else { return };
+    if window.height() <= 0.0 {
+        return;
+    }
+    for (transform, projection, shake) in query_camera.iter() {
+        if let Projection::Perspective(perspective) = projection {
+            // measured from the resting camera, not the shaking one
+            let camera = match shake {
+                Some(shake) => Transform { translation: shake.base, ..*transform },
+                None => *transform,
+            };
+            let visible = visible_bounds(&camera, perspective.fov, window.width() / window.height());
+            if *bounds != visible {
+                *bounds = visible;
+            }
+        }
+    }
+}
+
+fn ghost_transform(transform:&Transform, offset:IVec2, bounds:&ArenaBounds) -> Transform {
+    let size = bounds.size();
+    Transform {
+        translation: transform.translation + Vec3::new(offset.x as f32 * size.x, 0.0, offset.y as f32 * size.y),
+        ..*transform
+    }
+}
+
+fn update_ghosts(
+    mut commands: Commands,
+    bounds: Res<ArenaBounds>,
+    game_assets: Option<Res<GameAssets>>,
+    query_source: Query<(Entity, &Transform, &Collider, &Visibility,
+        Option<&Handle<Mesh>>, Option<&Handle<StandardMaterial>>, Option<&Ship>), Without<Ghost>>,
+    mut query_ghost: Query<(Entity, &Ghost, &mut Transform, &mut Visibility)>
+){
+    //ghosts needed by entities overlapping an edge
+    let mut needed:Vec<(Entity, IVec2)> = Vec::new();
+    for (entity, transform, collider, ..) in query_source.iter() {
+        let radius = collider.bounding_radius() * transform.scale.max_element();
+        let position = transform.translation;
+        let x = if position.x + radius > bounds.max.x { -1 } else if position.x - radius < bounds.min.x { 1 } else { 0 };
+        let z = if position.z + radius > bounds.max.y { -1 } else if position.z - radius < bounds.min.y { 1 } else { 0 };
+        for offset in [IVec2::new(x, 0), IVec2::new(0, z), IVec2::new(x, z)] {
+            if offset != IVec2::ZERO && !needed.contains(&(entity, offset)) {
+                needed.push((entity, offset));
+            }
+        }
+    }
+    //follow the source or vanish
+    for (ghost_entity, ghost, mut transform, mut visibility) in query_ghost.iter_mut() {
+        let index = needed.iter().position(|key| *key == (ghost.source, ghost.offset));
+        match (index, query_source.get(ghost.source)) {
+            (Some(index), Ok((_, source_transform, _, source_visibility, ..))) => {
+                needed.swap_remove(index);
+                *transform = ghost_transform(source_transform, ghost.offset, &bounds);
+                visibility.is_visible = source_visibility.is_visible;
+            },
+            _ => commands.entity(ghost_entity).despawn_recursive(),
+        }
+    }
+    //new ghosts, plain meshes even for the ship's scene
+    let Some(game_assets) = game_assets else { return };
+    for (source, offset) in needed {
+        let Ok((_, transform, _, visibility, mesh, material, ship)) = query_source.get(source) else { continue };
+        let mut ghost = commands.spawn(SpatialBundle {
+            transform: ghost_transform(transform, offset, &bounds),
+            visibility: Visibility { is_visible: visibility.is_visible },
+            ..Default::default()
+        });
+        ghost.insert(Ghost{source, offset});
+        if let (Some(mesh), Some(material)) = (mesh, material) {
+            ghost.insert((mesh.clone(), material.clone()));
+        } else if ship.is_some() {
+            ghost.with_children(|parent| {
+                for (mesh, material) in game_assets.ship_parts.iter() {
+                    parent.spawn(PbrBundle {
+                        mesh: mesh.clone(),
+                        material: material.clone(),
+                        transform: Transform::from_rotation(SHIP_MODEL_ROTATION),
+                        ..Default::default()
+                    });
+                }
+            });
+        }
+    }
+}
+
+fn camera_height(
+    config: Res<GameConfig>,
+    mut query: Query<(&mut CameraShake, &mut Transform)>
+){
+    if !config.is_changed() {
+        return;
+    }
+    for (mut shake, mut transform) in query.iter_mut(){
+        shake.base.y = config.camera_height;
+        *transform = Transform::from_translation(shake.base).looking_at(Vec3::ZERO, Vec3::Y);
+    }
+}
+
+fn camera_shake(
     time:Res<Time>,
-    mut query: Query<(&mut Shake, &mut Transform)>
+    config: Res<GameConfig>,
+    mut rng: ResMut<GameRng>,
+    mut query: Query<(&mut CameraShake, &mut Transform)>
 ){
     for (mut shake, mut transform) in query.iter_mut(){
-        if shake.value {
-            shake.time -= time.delta_seconds();
-            if shake.time < 0.0 {
-                shake.value = false;
-                shake.time = 0.0
+        if shake.trauma > 0.0 {
+            shake.trauma = (shake.trauma - config.shake_decay * time.delta_seconds()).max(0.0);
+            let rng = &mut rng.effects;
+            let amount = config.shake_variance * shake.trauma * shake.trauma;
+            transform.translation = shake.base + Vec3::new(rng.gen_range(-1.0..1.0) * amount,
+                                                            0.0,
+                                                            rng.gen_range(-1.0..1.0) * amount);
+        }
+    }
+}
+
+const BLINK_RATE:f32=10.0;
+
+fn invulnerability(
+    mut query: Query<(&mut Invulnerable, &mut Visibility)>
+){
+    for (mut invulnerable, mut visibility) in query.iter_mut(){
+        if invulnerable.value {
+            invulnerable.time -= TICK;
+            if invulnerable.time < 0.0 {
+                invulnerable.value = false;
+                invulnerable.time = 0.0;
+                visibility.is_visible = true;
             } else {
-                let mut rng = rand::thread_rng();
-                transform.translation = Vec3::new(rng.gen_range(0.0..SHAKE_VARIANCE),
-                                                  0.0,
-                                                  rng.gen_range(0.0..SHAKE_VARIANCE))
+                visibility.is_visible = (invulnerable.time * BLINK_RATE) as i32 % 2 == 0;
             }
         }
     }
 }
-
@@ -0,0 +1,213 @@
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::rng::GameRng;
+use crate::GameState;
+
+const PARTICLE_POOL:usize = 512;
+
+pub struct ParticlesPlugin;
+
+impl Plugin for ParticlesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup_particles)
+            .add_system_set(SystemSet::new()
+                .with_run_criteria(not_paused)
+                .with_system(emit)
+                .with_system(update_particles));
+    }
+}
+
+// one-shot burst, consumed on the next frame at the entity's transform
+#[derive(Component)]
+pub struct ParticleEmitter{
+    pub count:usize,
+    pub speed:f32,
+    pub lifetime:f32,
+    pub scale:f32,
+    pub color:Color,
+}
+
+impl ParticleEmitter {
+    pub fn asteroid(radius:f32) -> Self {
+        Self {
+            count: (radius * 24.0) as usize,
+            speed: 3.0,
+            lifetime: 0.8,
+            scale: radius * 0.25,
+            color: Color::rgb(0.9, 0.85, 0.8),
+        }
+    }
+
+    pub fn ship() -> Self {
+        Self {
+            count: 40,
+            speed: 4.0,
+            lifetime: 1.2,
+            scale: 0.2,
+            color: Color::ORANGE,
+        }
+    }
+
+    pub fn laser() -> Self {
+        Self {
+            count: 6,
+            speed: 1.0,
+            lifetime: 0.3,
+            scale: 0.08,
+            color: Color::LIME_GREEN,
+        }
+    }
+}
+
+#[derive(Component, Default)]
+pub struct Particle{
+    pub velocity:Vec3,
+    pub age:f32,
+    pub lifetime:f32,
+    pub start_scale:f32,
+    pub end_scale:f32,
+    pub active:bool,
+}
+
+impl Particle {
+    // advances the particle, returns false once its lifetime is over
+    pub fn update(&mut self, translation:&mut Vec3, dt:f32) -> bool {
+        self.age += dt;
+        *translation += self.velocity * dt;
+        self.velocity *= (1.0 - 2.0 * dt).max(0.0);
+        self.age < self.lifetime
+    }
+
+    fn progress(&self) -> f32 {
+        (self.age / self.lifetime).clamp(0.0, 1.0)
+    }
+
+    pub fn scale(&self) -> f32 {
+        let t = self.progress();
+        self.start_scale + (self.end_scale - self.start_scale) * t
+    }
+
+    pub fn alpha(&self) -> f32 {
+        let t = self.progress();
+        1.0 - t * t
+    }
+}
+
+// inactive particle entities ready for reuse
+#[derive(Resource)]
+pub struct ParticlePool{
+    pub free:Vec<Entity>
+}
+
+fn not_paused(state: Res<State<GameState>>) -> ShouldRun {
+    if *state.current() == GameState::Paused { ShouldRun::No } else { ShouldRun::Yes }
+}
+
+fn setup_particles(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+){
+    let mesh = meshes.add(Mesh::from(shape::Icosphere { radius: 1.0, subdivisions: 1 }));
+    let free = (0..PARTICLE_POOL).map(|_| {
+        commands.spawn(PbrBundle {
+            mesh: mesh.clone(),
+            // every pooled particle owns its material so it can fade on its own
+            material: materials.add(StandardMaterial {
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..Default::default()
+            }),
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        })
+        .insert(Particle::default())
+        .id()
+    }).collect();
+    commands.insert_resource(ParticlePool{free});
+}
+
+fn emit(
+    mut commands: Commands,
+    mut rng: ResMut<GameRng>,
+    mut pool: ResMut<ParticlePool>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    query_emitter: Query<(Entity, &ParticleEmitter, &Transform)>,
+    mut query_particle: Query<(&mut Particle, &mut Transform, &mut Visibility, &Handle<StandardMaterial>), Without<ParticleEmitter>>
+){
+    let rng = &mut rng.effects;
+    for (entity, emitter, emitter_transform) in query_emitter.iter() {
+        for _ in 0..emitter.count {
+            let Some(particle_entity) = pool.free.pop() else { break };
+            let Ok((mut particle, mut transform, mut visibility, material)) = query_particle.get_mut(particle_entity) else { continue };
+            let direction = Quat::from_rotation_y(rng.gen_range(0.0..std::f32::consts::TAU)) * Vec3::Z;
+            *particle = Particle {
+                velocity: direction * emitter.speed * rng.gen_range(0.3..1.0),
+                age: 0.0,
+                lifetime: emitter.lifetime * rng.gen_range(0.6..1.0),
+                start_scale: emitter.scale,
+                end_scale: emitter.scale * 0.2,
+                active: true,
+            };
+            *transform = Transform::from_translation(emitter_transform.translation)
+                .with_scale(Vec3::splat(emitter.scale));
+            visibility.is_visible = true;
+            if let Some(material) = materials.get_mut(material) {
+                material.base_color = emitter.color;
+            }
+        }
+        commands.entity(entity).despawn();
+    }
+}
+
+fn update_particles(
+    time:Res<Time>,
+    mut pool: ResMut<ParticlePool>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut query: Query<(Entity, &mut Particle, &mut Transform, &mut Visibility, &Handle<StandardMaterial>)>
+){
+    for (entity, mut particle, mut transform, mut visibility, material) in query.iter_mut() {
+        if !particle.active {
+            continue;
+        }
+        if particle.update(&mut transform.translation, time.delta_seconds()) {
+            transform.scale = Vec3::splat(particle.scale());
+            if let Some(material) = materials.get_mut(material) {
+                material.base_color.set_a(particle.alpha());
+            }
+        } else {
+            particle.active = false;
+            visibility.is_visible = false;
+            pool.free.push(entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn particle_lives_for_its_lifetime() {
+        let mut particle = Particle{velocity:Vec3::X, lifetime:1.0, start_scale:1.0, end_scale:0.2, active:true, ..default()};
+        let mut translation = Vec3::ZERO;
+        let mut frames = 0;
+        while particle.update(&mut translation, 0.1) {
+            frames += 1;
+        }
+        // the tenth step reaches the lifetime, give or take rounding
+        assert!((9..=10).contains(&frames), "lived {} frames", frames);
+        assert!(translation.x > 0.0 && translation.x < 1.0, "drag slows it down");
+        assert!((particle.scale() - 0.2).abs() < 1e-6);
+        assert_eq!(particle.alpha(), 0.0);
+    }
+
+    #[test]
+    fn fresh_particle_is_full_size_and_opaque() {
+        let particle = Particle{lifetime:1.0, start_scale:1.0, end_scale:0.2, active:true, ..default()};
+        assert_eq!(particle.scale(), 1.0);
+        assert_eq!(particle.alpha(), 1.0);
+    }
+}
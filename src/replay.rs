@@ -0,0 +1,402 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::actions::{SampleActions, TickActions};
+use crate::cli::CommandLine;
+use crate::config::{ConfigLoaded, GameConfig};
+use crate::fixed::{FixedSystem, FixedUpdate};
+use crate::rng::GameRng;
+use crate::{build_game_assets, playing, reset_game, setup_game, ArenaBounds, Asteroid, GameAssets, GameState, Laser, Score, Ship, Velocity, Wave};
+
+// --record writes the seed, config and the actions of every tick of a run to
+// a file, --replay feeds them back in place of the player and checks the world
+// ends up the same. the simulation only depends on those and the arena bounds
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        // the recording to replay was already read while parsing the command line
+        let command_line = app.world.resource::<CommandLine>();
+        let (record, replay) = (command_line.record.clone(), command_line.replay.clone());
+        if let Some(path) = record {
+            app.insert_resource(Recorder{path, recording:None})
+                .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(start_recording.after(reset_game)))
+                .add_system_to_stage(FixedUpdate, record_inputs
+                    .with_run_criteria(playing).after(SampleActions).before(FixedSystem::Simulate))
+                .add_system_to_stage(FixedUpdate, record_checksum
+                    .with_run_criteria(playing).after(FixedSystem::Simulate))
+                .add_system_to_stage(CoreStage::PostUpdate, finish_recording)
+                .add_system_to_stage(CoreStage::Last, save_on_exit);
+        }
+        if let Some(recording) = replay {
+            app.insert_resource(Replay::new(recording))
+                .add_system_set(SystemSet::on_update(GameState::MainMenu).with_system(start_replay.after(build_game_assets)))
+                .add_system_set(SystemSet::on_enter(GameState::Playing)
+                    .with_system(replay_bounds.after(reset_game).before(setup_game)))
+                .add_system_to_stage(FixedUpdate, replay_inputs
+                    .with_run_criteria(playing).after(SampleActions).before(FixedSystem::Simulate))
+                .add_system_to_stage(FixedUpdate, verify_replay
+                    .with_run_criteria(playing).after(FixedSystem::Simulate))
+                .add_system_to_stage(CoreStage::PostUpdate, replay_ended);
+        }
+    }
+}
+
+#[derive(Default, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Recording{
+    seed:u64,
+    // config.ron as it was when the run started
+    config:GameConfig,
+    ticks:u32,
+    // world state after the last tick
+    checksum:u64,
+    // the bounds from the tick they took effect, the first ones for the whole run
+    bounds:Vec<(u32, ArenaBounds)>,
+    // runs of ticks with the same actions
+    inputs:Vec<(u32, TickActions)>,
+}
+
+impl Recording {
+    pub fn load(path:&Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+        ron::from_str(&text).map_err(|err| format!("invalid replay {}: {}", path.display(), err))
+    }
+
+    fn save(&self, path:&Path) {
+        let result = ron::to_string(self)
+            .map_err(|err| err.to_string())
+            .and_then(|text| std::fs::write(path, text).map_err(|err| err.to_string()));
+        match result {
+            Ok(()) => info!("recorded {} ticks to {}", self.ticks, path.display()),
+            Err(err) => warn!("could not save the recording {}: {}", path.display(), err),
+        }
+    }
+
+    // adds the next tick, played in `bounds` with `actions`
+    fn push(&mut self, bounds:ArenaBounds, actions:&TickActions) {
+        if self.bounds.last().map(|(_, last)| *last) != Some(bounds) {
+            self.bounds.push((self.ticks, bounds));
+        }
+        match self.inputs.last_mut() {
+            Some((count, last)) if last == actions => *count += 1,
+            _ => self.inputs.push((1, actions.clone())),
+        }
+        self.ticks += 1;
+    }
+}
+
+// the run in progress, each new run overwrites the file of the last one
+#[derive(Resource)]
+struct Recorder{
+    path:PathBuf,
+    recording:Option<Recording>,
+}
+
+impl Recorder {
+    fn finish(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            if recording.ticks > 0 {
+                recording.save(&self.path);
+            }
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct Replay{
+    recording:Recording,
+    // ticks played so far
+    tick:u32,
+    // position in recording.inputs, and ticks used of that entry
+    input:usize,
+    input_tick:u32,
+    // next entry of recording.bounds
+    bounds:usize,
+    started:bool,
+    finished:bool,
+    // the world after the last tick matched the recording
+    matched:bool,
+}
+
+impl Replay {
+    fn new(recording:Recording) -> Self {
+        Self{recording, tick:0, input:0, input_tick:0, bounds:0, started:false, finished:false, matched:false}
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.recording.seed
+    }
+
+    // the bounds if they change on the next tick, and its actions.
+    // the player has no say, past the end nothing is pressed
+    fn next_tick(&mut self) -> (Option<ArenaBounds>, TickActions) {
+        let mut bounds = None;
+        while let Some((tick, recorded)) = self.recording.bounds.get(self.bounds) {
+            if *tick > self.tick {
+                break;
+            }
+            bounds = Some(*recorded);
+            self.bounds += 1;
+        }
+        self.tick += 1;
+        let Some((count, recorded)) = self.recording.inputs.get(self.input) else {
+            return (bounds, TickActions::default());
+        };
+        let actions = recorded.clone();
+        self.input_tick += 1;
+        if self.input_tick == *count {
+            self.input += 1;
+            self.input_tick = 0;
+        }
+        (bounds, actions)
+    }
+}
+
+// what the simulation produced, in no particular order
+type Simulated<'w, 's> = Query<'w, 's, (&'static Transform, &'static Velocity), Or<(With<Ship>, With<Asteroid>, With<Laser>)>>;
+
+fn checksum(score:&Score, wave:&Wave, query:&Simulated) -> u64 {
+    let mut checksum = fnv(&[score.value as u32, score.ships as u32, wave.value as u32]);
+    for (transform, velocity) in query.iter() {
+        let (t, r, v) = (transform.translation, transform.rotation, velocity.0);
+        checksum = checksum.wrapping_add(fnv(&[t.x, t.y, t.z, r.x, r.y, r.z, r.w, v.x, v.y, v.z].map(f32::to_bits)));
+    }
+    checksum
+}
+
+// FNV-1a, unlike the std hasher it stays the same between builds
+fn fnv(words:&[u32]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for byte in words.iter().flat_map(|word| word.to_le_bytes()) {
+        hash = (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+fn start_recording(
+    rng: Res<GameRng>,
+    config: Res<GameConfig>,
+    bounds: Res<ArenaBounds>,
+    mut recorder: ResMut<Recorder>,
+){
+    recorder.recording = Some(Recording{seed:rng.seed, config:config.clone(), bounds:vec![(0, *bounds)], ..default()});
+}
+
+fn record_inputs(
+    tick_actions: Res<TickActions>,
+    bounds: Res<ArenaBounds>,
+    mut recorder: ResMut<Recorder>,
+){
+    if let Some(recording) = recorder.recording.as_mut() {
+        recording.push(*bounds, &tick_actions);
+    }
+}
+
+fn record_checksum(
+    score: Res<Score>,
+    wave: Res<Wave>,
+    mut recorder: ResMut<Recorder>,
+    query: Simulated,
+){
+    if let Some(recording) = recorder.recording.as_mut() {
+        recording.checksum = checksum(&score, &wave, &query);
+    }
+}
+
+fn finish_recording(
+    state: Res<State<GameState>>,
+    mut recorder: ResMut<Recorder>,
+){
+    if !matches!(state.current(), GameState::Playing | GameState::Paused) {
+        recorder.finish();
+    }
+}
+
+// closing the window keeps the run up to there
+fn save_on_exit(
+    exit: EventReader<AppExit>,
+    mut recorder: ResMut<Recorder>,
+){
+    if !exit.is_empty() {
+        recorder.finish();
+    }
+}
+
+// once config.ron is in place it's replaced by the recorded config, the
+// menu is skipped on the next frame when the assets were rebuilt from that
+fn start_replay(
+    mut state: ResMut<State<GameState>>,
+    config_loaded: Res<ConfigLoaded>,
+    mut config: ResMut<GameConfig>,
+    game_assets: Option<Res<GameAssets>>,
+    mut replay: ResMut<Replay>,
+    mut config_applied: Local<bool>,
+){
+    if replay.started || !config_loaded.0 {
+        return;
+    }
+    if !*config_applied {
+        let changes = config.changes(&replay.recording.config);
+        if !changes.is_empty() {
+            info!("replaying with the recorded config, changed {}", changes.join(", "));
+        }
+        *config = replay.recording.config.clone();
+        *config_applied = true;
+        return;
+    }
+    if game_assets.is_none() {
+        return;
+    }
+    replay.started = true;
+    info!("replaying {} ticks", replay.recording.ticks);
+    let _ = state.set(GameState::Playing);
+}
+
+fn replay_bounds(
+    mut bounds: ResMut<ArenaBounds>,
+    mut replay: ResMut<Replay>,
+){
+    if let Some((_, recorded)) = replay.recording.bounds.first() {
+        *bounds = *recorded;
+        replay.bounds = 1;
+    }
+}
+
+fn replay_inputs(
+    mut tick_actions: ResMut<TickActions>,
+    mut bounds: ResMut<ArenaBounds>,
+    mut replay: ResMut<Replay>,
+){
+    let (recorded_bounds, actions) = replay.next_tick();
+    if let Some(recorded_bounds) = recorded_bounds {
+        *bounds = recorded_bounds;
+    }
+    *tick_actions = actions;
+}
+
+fn verify_replay(
+    score: Res<Score>,
+    wave: Res<Wave>,
+    mut replay: ResMut<Replay>,
+    mut exit: EventWriter<AppExit>,
+    query: Simulated,
+){
+    if replay.finished || replay.tick < replay.recording.ticks {
+        return;
+    }
+    replay.finished = true;
+    let checksum = checksum(&score, &wave, &query);
+    replay.matched = checksum == replay.recording.checksum;
+    if replay.matched {
+        info!("replay matched the recording after {} ticks", replay.tick);
+    } else {
+        error!("replay diverged from the recording, checksum {:016x} instead of {:016x}",
+            checksum, replay.recording.checksum);
+    }
+    exit.send(AppExit);
+}
+
+// the recorded run lasted longer than the replayed one
+fn replay_ended(
+    state: Res<State<GameState>>,
+    mut replay: ResMut<Replay>,
+    mut exit: EventWriter<AppExit>,
+){
+    if replay.started && !replay.finished && !matches!(state.current(), GameState::Playing | GameState::Paused) {
+        replay.finished = true;
+        error!("replay diverged from the recording, the run ended after {} of {} ticks",
+            replay.tick, replay.recording.ticks);
+        exit.send(AppExit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::Action;
+    use crate::fixed::TICK;
+    use crate::tests::{firing_script, Headless};
+
+    fn bounds(size:f32) -> ArenaBounds {
+        ArenaBounds{min:Vec2::splat(-size), max:Vec2::splat(size)}
+    }
+
+    #[test]
+    fn fnv_matches_the_reference() {
+        assert_eq!(fnv(&[]), 0xcbf2_9ce4_8422_2325);
+        // the bytes of "abcdefgh"
+        assert_eq!(fnv(&[0x6463_6261, 0x6867_6665]), 0x25da_8c18_36a8_d66d);
+    }
+
+    #[test]
+    fn recording_survives_ron() {
+        let mut recording = Recording{seed:42, config:GameConfig{turn_speed:2.5, seed:Some(3), ..default()}, checksum:7, ..default()};
+        recording.push(bounds(5.0), &TickActions::new(&[(Action::RotateLeft, 0.3)], &[Action::Fire]));
+        recording.push(bounds(6.0), &TickActions::default());
+        let text = ron::to_string(&recording).unwrap();
+        assert_eq!(ron::from_str::<Recording>(&text).unwrap(), recording);
+    }
+
+    #[test]
+    fn inputs_are_packed_into_runs_and_unpacked() {
+        let thrust = TickActions::new(&[(Action::Thrust, 1.0)], &[]);
+        let fire = TickActions::new(&[], &[Action::Fire]);
+        let ticks = [(bounds(5.0), &thrust), (bounds(5.0), &thrust), (bounds(5.0), &fire),
+            (bounds(6.0), &fire), (bounds(6.0), &fire), (bounds(6.0), &thrust)];
+        let mut recording = Recording::default();
+        for (bounds, actions) in ticks {
+            recording.push(bounds, actions);
+        }
+        assert_eq!(recording.ticks, 6);
+        assert_eq!(recording.inputs, vec![(2, thrust.clone()), (3, fire.clone()), (1, thrust.clone())]);
+        assert_eq!(recording.bounds, vec![(0, bounds(5.0)), (3, bounds(6.0))]);
+
+        let mut replay = Replay::new(recording);
+        let mut current = None;
+        for (bounds, actions) in ticks {
+            let (changed, replayed) = replay.next_tick();
+            current = changed.or(current);
+            assert_eq!(current, Some(bounds));
+            assert_eq!(replayed, *actions);
+        }
+        assert_eq!(replay.next_tick(), (None, TickActions::default()));
+    }
+
+    // records the firing script at 60 fps until the run is ended
+    fn record(config:GameConfig) -> Recording {
+        let path = std::env::temp_dir().join(format!("bevyastro-record-{}.ron", std::process::id()));
+        let mut game = Headless::new(60.0, config);
+        game.app.insert_resource(CommandLine{seed:Some(1), record:Some(path.clone()), replay:None})
+            .add_plugin(ReplayPlugin);
+        game.start(firing_script());
+        game.play();
+        game.app.world.resource_mut::<State<GameState>>().set(GameState::GameOver).unwrap();
+        game.update();
+        let recording = Recording::load(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        recording
+    }
+
+    #[test]
+    fn replay_matches_the_recording_at_any_frame_rate() {
+        let recording = record(GameConfig{ships:1000, asteroid_size:0.7, ..default()});
+        assert!(recording.inputs.len() > 1);
+        for fps in [10.0, 240.0] {
+            // config.ron differs from the recorded config, the replay has to use the latter
+            let mut game = Headless::new(fps, GameConfig::default());
+            game.app.insert_resource(CommandLine{replay:Some(recording.clone()), ..default()})
+                .insert_resource(ConfigLoaded(true))
+                .add_plugin(ReplayPlugin);
+            let frames = (recording.ticks as f64 * TICK as f64 * fps) as usize + 10;
+            for _ in 0..frames {
+                game.update();
+            }
+            let replay = game.app.world.resource::<Replay>();
+            assert!(replay.finished, "{} fps", fps);
+            assert!(replay.matched, "{} fps", fps);
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use bevy::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+// every random number in the game comes from here. gameplay and effects
+// draw from separate streams, so camera shake and particles, which run
+// per rendered frame, can't shift the asteroids of a seeded run.
+// ChaCha8 gives the same numbers on every platform and rand version,
+// StdRng makes no such promise
+#[derive(Resource)]
+pub struct GameRng{
+    pub seed:u64,
+    pub gameplay:ChaCha8Rng,
+    pub effects:ChaCha8Rng,
+}
+
+impl GameRng {
+    pub fn new(seed:u64) -> Self {
+        Self {
+            seed,
+            gameplay: ChaCha8Rng::seed_from_u64(seed),
+            effects: ChaCha8Rng::seed_from_u64(seed ^ 0x9E37_79B9_7F4A_7C15),
+        }
+    }
+}
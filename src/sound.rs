@@ -0,0 +1,132 @@
+use bevy::audio::AudioSink;
+use bevy::prelude::*;
+
+use crate::AsteroidSize;
+
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SoundEvent>()
+            .insert_resource(VolumeSettings::default())
+            .add_startup_system(setup_sounds)
+            // after the gameplay systems so every event of the frame is seen
+            .add_system_to_stage(CoreStage::PostUpdate, play_sounds)
+            .add_system_to_stage(CoreStage::PostUpdate, loop_volume.after(play_sounds));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SoundEvent {
+    Laser,
+    Explosion(AsteroidSize),
+    ShipHit,
+    // sent every tick the ship is thrusting
+    Thrust,
+}
+
+#[derive(Resource)]
+pub struct VolumeSettings{
+    pub master:f32,
+    pub sfx:f32,
+    pub music:f32,
+}
+
+impl Default for VolumeSettings{
+    fn default() -> Self {
+        Self {
+            master:1.0,
+            sfx:0.8,
+            music:0.5,
+        }
+    }
+}
+
+impl VolumeSettings{
+    pub fn sfx_volume(&self) -> f32 {
+        self.master * self.sfx
+    }
+
+    pub fn music_volume(&self) -> f32 {
+        self.master * self.music
+    }
+}
+
+#[derive(Resource)]
+struct Sounds{
+    laser:Handle<AudioSource>,
+    explosion:Handle<AudioSource>,
+    hit:Handle<AudioSource>,
+    thrust_sink:Handle<AudioSink>,
+    music_sink:Handle<AudioSink>,
+    // seconds the thrust loop keeps playing, bridges frames without a tick
+    thrust_hold:f32,
+}
+
+fn setup_sounds(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    audio_sinks: Res<Assets<AudioSink>>,
+){
+    // both loops run for the whole session, their volume is driven by loop_volume
+    let thrust = audio.play_with_settings(asset_server.load("sounds/thrust.wav"),
+        PlaybackSettings::LOOP.with_volume(0.0));
+    let music = audio.play_with_settings(asset_server.load("sounds/music.wav"),
+        PlaybackSettings::LOOP.with_volume(0.0));
+
+    commands.insert_resource(Sounds{
+        laser: asset_server.load("sounds/laser.wav"),
+        explosion: asset_server.load("sounds/explosion.wav"),
+        hit: asset_server.load("sounds/hit.wav"),
+        thrust_sink: audio_sinks.get_handle(thrust),
+        music_sink: audio_sinks.get_handle(music),
+        thrust_hold: 0.0,
+    });
+}
+
+const THRUST_HOLD:f32 = 0.05;
+
+fn play_sounds(
+    time: Res<Time>,
+    audio: Res<Audio>,
+    volume: Res<VolumeSettings>,
+    mut sounds: ResMut<Sounds>,
+    mut events: EventReader<SoundEvent>,
+){
+    let sfx = volume.sfx_volume();
+    sounds.thrust_hold = (sounds.thrust_hold - time.delta_seconds()).max(0.0);
+    for event in events.iter() {
+        let (source, settings) = match event {
+            SoundEvent::Laser => (&sounds.laser, PlaybackSettings::ONCE.with_volume(sfx * 0.5)),
+            // bigger rocks sound deeper and louder
+            SoundEvent::Explosion(size) => {
+                let (speed, gain) = match size {
+                    AsteroidSize::Large => (0.7, 1.0),
+                    AsteroidSize::Medium => (1.0, 0.8),
+                    AsteroidSize::Small => (1.4, 0.6),
+                };
+                (&sounds.explosion, PlaybackSettings::ONCE.with_volume(sfx * gain).with_speed(speed))
+            },
+            SoundEvent::ShipHit => (&sounds.hit, PlaybackSettings::ONCE.with_volume(sfx)),
+            SoundEvent::Thrust => {
+                sounds.thrust_hold = THRUST_HOLD;
+                continue;
+            },
+        };
+        audio.play_with_settings(source.clone(), settings);
+    }
+}
+
+fn loop_volume(
+    volume: Res<VolumeSettings>,
+    sounds: Res<Sounds>,
+    audio_sinks: Res<Assets<AudioSink>>,
+){
+    if let Some(sink) = audio_sinks.get(&sounds.thrust_sink) {
+        sink.set_volume(if sounds.thrust_hold > 0.0 { volume.sfx_volume() * 0.4 } else { 0.0 });
+    }
+    if let Some(sink) = audio_sinks.get(&sounds.music_sink) {
+        sink.set_volume(volume.music_volume());
+    }
+}
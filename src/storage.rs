@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+// where small ron files like the bindings and high scores are kept.
+// native builds use the user's directories, the web build the browser's local storage
+pub trait Storage {
+    fn read(&self, name:&str) -> Option<String>;
+    fn write(&self, name:&str, text:&str) -> Result<(), String>;
+    // where the file lives, for log messages
+    fn describe(&self, name:&str) -> String;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub struct FileStorage{
+    dir:Option<std::path::PathBuf>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Storage for FileStorage {
+    fn read(&self, name:&str) -> Option<String> {
+        std::fs::read_to_string(self.dir.as_ref()?.join(name)).ok()
+    }
+
+    fn write(&self, name:&str, text:&str) -> Result<(), String> {
+        let dir = self.dir.as_ref().ok_or("no user directory")?;
+        std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        std::fs::write(dir.join(name), text).map_err(|err| err.to_string())
+    }
+
+    fn describe(&self, name:&str) -> String {
+        match &self.dir {
+            Some(dir) => dir.join(name).display().to_string(),
+            None => name.to_string(),
+        }
+    }
+}
+
+// user settings like the key bindings
+#[cfg(not(target_arch = "wasm32"))]
+pub fn settings() -> impl Storage {
+    FileStorage{dir: dirs::config_dir().map(|dir| dir.join("bevyastro"))}
+}
+
+// game data like the high scores
+#[cfg(not(target_arch = "wasm32"))]
+pub fn data() -> impl Storage {
+    FileStorage{dir: dirs::data_dir().map(|dir| dir.join("bevyastro"))}
+}
+
+#[cfg(target_arch = "wasm32")]
+pub struct BrowserStorage;
+
+#[cfg(target_arch = "wasm32")]
+impl BrowserStorage {
+    fn local_storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Storage for BrowserStorage {
+    fn read(&self, name:&str) -> Option<String> {
+        Self::local_storage()?.get_item(&self.describe(name)).ok()?
+    }
+
+    fn write(&self, name:&str, text:&str) -> Result<(), String> {
+        let storage = Self::local_storage().ok_or("no local storage")?;
+        storage.set_item(&self.describe(name), text).map_err(|err| format!("{:?}", err))
+    }
+
+    fn describe(&self, name:&str) -> String {
+        format!("bevyastro/{}", name)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn settings() -> impl Storage {
+    BrowserStorage
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn data() -> impl Storage {
+    BrowserStorage
+}
+
+pub fn load<T: DeserializeOwned>(storage:&impl Storage, name:&str) -> Option<T> {
+    let text = storage.read(name)?;
+    match ron::from_str(&text) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            warn!("ignoring {}: {}", storage.describe(name), err);
+            None
+        }
+    }
+}
+
+pub fn save<T: Serialize>(storage:&impl Storage, name:&str, value:&T) {
+    let result = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(|err| err.to_string())
+        .and_then(|text| storage.write(name, &text));
+    if let Err(err) = result {
+        warn!("could not save {}: {}", storage.describe(name), err);
+    }
+}
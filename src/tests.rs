@@ -0,0 +1,369 @@
+// headless runs of the game, the window, rendering and sound left out
+use bevy::core::CorePlugin;
+use bevy::time::{TimePlugin, TimeUpdateStrategy};
+use bevy::utils::{Duration, Instant};
+use bevy::window::Windows;
+
+use super::*;
+use crate::actions::SampleActions;
+
+// the actions of each tick, nothing is pressed after the end
+#[derive(Resource, Default)]
+struct Script{
+    ticks:Vec<TickActions>,
+    tick:usize,
+}
+
+impl Script {
+    fn finished(&self) -> bool {
+        self.tick >= self.ticks.len()
+    }
+}
+
+fn play_script(
+    mut script: ResMut<Script>,
+    mut tick_actions: ResMut<TickActions>,
+){
+    *tick_actions = script.ticks.get(script.tick).cloned().unwrap_or_default();
+    script.tick += 1;
+}
+
+// what the scripted ticks produced, later ticks are left out
+#[derive(Resource, Default)]
+struct Log{
+    sounds:Vec<SoundEvent>,
+    score:i32,
+    // pose and velocity of everything simulated after the last scripted tick
+    world:Vec<(Vec3, Quat, Vec3)>,
+}
+
+fn listen(
+    script: Res<Script>,
+    score: Res<Score>,
+    mut events: EventReader<SoundEvent>,
+    mut log: ResMut<Log>,
+    query: Query<(&Transform, &Velocity), Or<(With<Ship>, With<Asteroid>, With<Laser>)>>,
+){
+    if script.tick > script.ticks.len() {
+        events.clear();
+        return;
+    }
+    log.sounds.extend(events.iter().copied());
+    log.score = score.value;
+    log.world = query.iter().map(|(transform, velocity)| (transform.translation, transform.rotation, velocity.0)).collect();
+}
+
+pub struct Headless{
+    pub app:App,
+    frame:Duration,
+    now:Instant,
+}
+
+impl Headless {
+    pub fn new(fps:f64, config:GameConfig) -> Self {
+        let mut app = App::new();
+        app.add_plugin(CorePlugin::default())
+            .add_plugin(TimePlugin)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .add_asset::<StandardMaterial>()
+            .insert_resource(Windows::default())
+            .insert_resource(config)
+            .insert_resource(CommandLine{seed:Some(1), ..default()})
+            .init_resource::<HighScores>()
+            .init_resource::<TickActions>()
+            .init_resource::<Script>()
+            .init_resource::<Log>()
+            .add_event::<SoundEvent>()
+            .add_plugin(FixedUpdatePlugin)
+            .add_state(GameState::MainMenu)
+            .add_plugin(GamePlugin)
+            // the script stands in for the player's sampled actions
+            .add_system_to_stage(FixedUpdate, play_script.with_run_criteria(playing).label(SampleActions).before(FixedSystem::Simulate))
+            .add_system_to_stage(FixedUpdate, listen.with_run_criteria(playing).after(FixedSystem::Simulate));
+        let now = Instant::now();
+        app.insert_resource(TimeUpdateStrategy::ManualInstant(now));
+        Self{app, frame:Duration::from_secs_f64(1.0 / fps), now}
+    }
+
+    pub fn update(&mut self) {
+        self.now += self.frame;
+        *self.app.world.resource_mut::<TimeUpdateStrategy>() = TimeUpdateStrategy::ManualInstant(self.now);
+        self.app.update();
+    }
+
+    // the menu frame builds the shared assets the run spawns from
+    pub fn start(&mut self, script:Vec<TickActions>) {
+        self.update();
+        self.app.insert_resource(Script{ticks:script, tick:0});
+        self.app.world.resource_mut::<State<GameState>>().set(GameState::Playing).unwrap();
+        self.update();
+    }
+
+    // runs the frames until every scripted tick was simulated
+    pub fn play(&mut self) {
+        let ticks = self.app.world.resource::<Script>().ticks.len() as f64;
+        let frames = (ticks * TICK as f64 / self.frame.as_secs_f64()).ceil() as usize + 10;
+        for _ in 0..frames {
+            if self.app.world.resource::<Script>().finished() {
+                return;
+            }
+            self.update();
+        }
+        panic!("the run ended before the script");
+    }
+}
+
+#[test]
+fn firing_shares_the_laser_mesh() {
+    let config = GameConfig{max_laser:1000, ships:1000, ..default()};
+    let mut game = Headless::new(60.0, config);
+    game.start(vec![TickActions::new(&[], &[Action::Fire]); 1000]);
+    let meshes = game.app.world.resource::<Assets<Mesh>>().len();
+    game.play();
+    let fired = game.app.world.resource::<Log>().sounds.iter().filter(|sound| **sound == SoundEvent::Laser).count();
+    assert_eq!(fired, 1000);
+    assert_eq!(game.app.world.resource::<Assets<Mesh>>().len(), meshes);
+}
+
+#[test]
+fn no_more_than_max_laser_fly_at_once() {
+    for max_laser in [0, 2] {
+        let mut game = Headless::new(60.0, GameConfig{max_laser, ..default()});
+        game.start(vec![TickActions::new(&[], &[Action::Fire]); 20]);
+        // out of the way, so no shot is used up on a hit
+        place_asteroid(&mut game.app, AsteroidSize::Small, Vec3::new(-10.0, 0.0, -7.0));
+        game.play();
+        let fired = game.app.world.resource::<Log>().sounds.iter().filter(|sound| **sound == SoundEvent::Laser).count();
+        assert_eq!(fired, max_laser as usize);
+    }
+}
+
+#[test]
+fn particles_are_reused_from_the_pool() {
+    let mut game = Headless::new(60.0, GameConfig::default());
+    game.app.add_plugin(ParticlesPlugin);
+    game.update();
+    let particles = |app:&mut App| {
+        let mut query = app.world.query::<(Entity, &particles::Particle)>();
+        let mut all:Vec<(Entity, bool)> = query.iter(&app.world).map(|(entity, particle)| (entity, particle.active)).collect();
+        all.sort();
+        all
+    };
+    let pooled:Vec<Entity> = particles(&mut game.app).iter().map(|(entity, _)| *entity).collect();
+    for _ in 0..2 {
+        // more than the pool holds, the rest of the burst is dropped
+        let emitter = ParticleEmitter{count:pooled.len() + 100, ..ParticleEmitter::ship()};
+        game.app.world.spawn((emitter, Transform::default()));
+        game.update();
+        let active = particles(&mut game.app);
+        assert!(active.iter().all(|(_, active)| *active));
+        assert_eq!(active.len(), pooled.len());
+        for _ in 0..120 {
+            game.update();
+        }
+        let expired = particles(&mut game.app);
+        assert!(expired.iter().all(|(_, active)| !*active));
+        assert_eq!(expired.iter().map(|(entity, _)| *entity).collect::<Vec<_>>(), pooled);
+        assert_eq!(game.app.world.resource::<particles::ParticlePool>().free.len(), pooled.len());
+    }
+}
+
+// replaces the wave with one still rock on the given spot
+fn place_asteroid(app:&mut App, size:AsteroidSize, position:Vec3) {
+    let asteroids:Vec<Entity> = app.world.query_filtered::<Entity, With<Asteroid>>().iter(&app.world).collect();
+    for entity in asteroids {
+        app.world.despawn(entity);
+    }
+    app.world.spawn((
+        Transform::from_translation(position),
+        Velocity(Vec3::ZERO),
+        Asteroid{size, key:0},
+        Collider::Sphere{radius:size.radius(GameConfig::default().asteroid_size)},
+    ));
+}
+
+#[test]
+fn laser_hit_sounds_an_explosion() {
+    let mut game = Headless::new(60.0, GameConfig::default());
+    game.start(vec![]);
+    // the ship starts at the origin facing +z
+    place_asteroid(&mut game.app, AsteroidSize::Medium, Vec3::new(0.0, 0.0, 4.0));
+    let mut script = vec![TickActions::new(&[], &[Action::Fire])];
+    script.resize(30, TickActions::default());
+    game.app.insert_resource(Script{ticks:script, tick:0});
+    game.play();
+    let heard = &game.app.world.resource::<Log>().sounds;
+    assert!(heard.contains(&SoundEvent::Laser));
+    assert!(heard.contains(&SoundEvent::Explosion(AsteroidSize::Medium)), "heard {:?}", heard);
+}
+
+// turns slowly and fires twice a second, then waits for the last lasers
+pub fn firing_script() -> Vec<TickActions> {
+    let mut script:Vec<TickActions> = (0..600)
+        .map(|tick| match tick % 30 {
+            0 => TickActions::new(&[(Action::RotateLeft, 0.3)], &[Action::Fire]),
+            _ => TickActions::new(&[(Action::RotateLeft, 0.3)], &[]),
+        })
+        .collect();
+    script.resize(720, TickActions::default());
+    script
+}
+
+#[test]
+fn rocks_too_big_to_keep_clear_still_spawn() {
+    let mut game = Headless::new(60.0, GameConfig{asteroid_size:20.0, ..default()});
+    game.start(vec![TickActions::default()]);
+    let asteroids = game.app.world.query_filtered::<(), With<Asteroid>>().iter(&game.app.world).count();
+    assert_eq!(asteroids, GameConfig::default().wave.count(1) as usize);
+}
+
+#[test]
+fn ship_hits_a_rock_across_the_edge() {
+    let mut game = Headless::new(60.0, GameConfig::default());
+    game.start(vec![TickActions::default(); 10]);
+    let bounds = *game.app.world.resource::<ArenaBounds>();
+    // the rock straddles the left edge and shows up just past the right one,
+    // a unit from the ship the short way round, the whole arena the long way
+    place_asteroid(&mut game.app, AsteroidSize::Medium, Vec3::new(bounds.min.x + 0.2, 0.0, 0.0));
+    let ship = game.app.world.query_filtered::<Entity, With<Ship>>().single(&game.app.world);
+    game.app.world.entity_mut(ship)
+        .insert(Transform::from_xyz(bounds.max.x - 0.8, 0.0, 0.0))
+        .remove::<fixed::Interpolated>();
+    game.play();
+    assert!(game.app.world.resource::<Log>().sounds.contains(&SoundEvent::ShipHit));
+}
+
+#[test]
+fn hits_do_not_depend_on_the_frame_rate() {
+    let config = GameConfig{ships:1000, ..default()};
+    let mut outcomes = Vec::new();
+    for fps in [10.0, 240.0] {
+        let mut game = Headless::new(fps, config.clone());
+        game.start(firing_script());
+        game.play();
+        let log = game.app.world.resource::<Log>();
+        let explosions:Vec<SoundEvent> = log.sounds.iter().copied().filter(|sound| matches!(sound, SoundEvent::Explosion(_))).collect();
+        outcomes.push((explosions, log.score));
+    }
+    assert!(!outcomes[0].0.is_empty(), "the script should hit something");
+    assert_eq!(outcomes[0], outcomes[1]);
+}
+
+#[test]
+fn gameplay_does_not_depend_on_the_frame_rate() {
+    let config = GameConfig{ships:1000, ..default()};
+    // thrusting around while turning and firing, the ship wraps over the edges
+    // and jumps once
+    let mut script:Vec<TickActions> = (0..900)
+        .map(|tick| match (tick / 120 % 2, tick % 20) {
+            (0, 0) => TickActions::new(&[(Action::Thrust, 1.0), (Action::RotateRight, 0.5)], &[Action::Fire]),
+            (0, _) => TickActions::new(&[(Action::Thrust, 1.0), (Action::RotateRight, 0.5)], &[]),
+            (_, 0) => TickActions::new(&[(Action::RotateLeft, 1.0)], &[Action::Fire]),
+            _ => TickActions::new(&[(Action::RotateLeft, 1.0)], &[]),
+        })
+        .collect();
+    script[450] = TickActions::new(&[], &[Action::Hyperspace]);
+    let mut logs = Vec::new();
+    for fps in [30.0, 144.0] {
+        let mut game = Headless::new(fps, config.clone());
+        game.start(script.clone());
+        game.play();
+        let log = game.app.world.remove_resource::<Log>().unwrap();
+        logs.push((log.sounds, log.score, log.world));
+    }
+    assert!(!logs[0].2.is_empty());
+    assert_eq!(logs[0], logs[1]);
+}
+
+// config.ron is loaded from `folder`, returns once it was applied
+fn load_config(folder:&std::path::Path, watch_for_changes:bool) -> App {
+    let mut app = App::new();
+    app.add_plugin(CorePlugin::default())
+        .add_plugin(AssetPlugin{asset_folder:folder.display().to_string(), watch_for_changes})
+        .add_plugin(config::ConfigPlugin);
+    wait_for(&mut app, |app| app.world.resource::<config::ConfigLoaded>().0);
+    app
+}
+
+fn wait_for(app:&mut App, done:impl Fn(&App) -> bool) {
+    for _ in 0..500 {
+        app.update();
+        if done(app) {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    panic!("timed out");
+}
+
+// a copy of the shipped assets/config.ron with one value changed
+fn config_folder(name:&str, from:&str, to:&str) -> std::path::PathBuf {
+    let shipped = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/config.ron")).unwrap();
+    assert!(shipped.contains(from));
+    let folder = std::env::temp_dir().join(format!("bevyastro-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&folder).unwrap();
+    std::fs::write(folder.join("config.ron"), shipped.replace(from, to)).unwrap();
+    folder
+}
+
+#[test]
+fn shipped_config_loads() {
+    let app = load_config(std::path::Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/assets")), false);
+    let asset_server = app.world.resource::<AssetServer>();
+    let handle = asset_server.get_handle::<GameConfig, _>("config.ron");
+    assert_eq!(asset_server.get_load_state(handle), bevy::asset::LoadState::Loaded);
+}
+
+#[test]
+fn config_file_replaces_the_defaults() {
+    let folder = config_folder("load", "turn_speed: 3.1415927", "turn_speed: 2.5");
+    let app = load_config(&folder, false);
+    assert_eq!(app.world.resource::<GameConfig>().turn_speed, 2.5);
+    std::fs::remove_dir_all(folder).unwrap();
+}
+
+#[test]
+fn editing_the_config_retunes_live_entities() {
+    let folder = config_folder("watch", "asteroid_size: 0.5", "asteroid_size: 0.5");
+    let mut app = load_config(&folder, true);
+    app.insert_resource(Wave{value:1})
+        .add_system(retune);
+    let ship = app.world.spawn((Ship, Invulnerable{value:false, default_time:2.0, time:0.0})).id();
+    let asteroid = app.world.spawn((Asteroid{size:AsteroidSize::Large, key:0}, Transform::default(), Velocity(Vec3::X))).id();
+    app.update();
+
+    let shipped = std::fs::read_to_string(folder.join("config.ron")).unwrap();
+    let edited = shipped.replace("asteroid_size: 0.5", "asteroid_size: 1.0")
+        .replace("invulnerable_time: 2.0", "invulnerable_time: 3.0")
+        .replace("base_speed: 1.0", "base_speed: 2.0")
+        .replace("max_speed: 3.0", "max_speed: 4.0");
+    std::fs::write(folder.join("config.ron"), edited).unwrap();
+    wait_for(&mut app, |app| app.world.resource::<GameConfig>().asteroid_size == 1.0);
+
+    assert_eq!(app.world.get::<Transform>(asteroid).unwrap().scale, Vec3::splat(2.0));
+    assert_eq!(app.world.get::<Velocity>(asteroid).unwrap().0, Vec3::X * 2.0);
+    assert_eq!(app.world.get::<Invulnerable>(ship).unwrap().default_time, 3.0);
+    std::fs::remove_dir_all(folder).unwrap();
+}
+
+#[test]
+fn broken_config_keeps_the_last_good_one() {
+    let folder = config_folder("broken", "turn_speed: 3.1415927", "turn_speed: 2.5");
+    let mut app = load_config(&folder, true);
+    let failed = |app:&App| {
+        let asset_server = app.world.resource::<AssetServer>();
+        asset_server.get_load_state(asset_server.get_handle::<GameConfig, _>("config.ron")) == bevy::asset::LoadState::Failed
+    };
+    let shipped = std::fs::read_to_string(folder.join("config.ron")).unwrap();
+    for broken in [shipped.replace("turn_speed: 2.5", "turn_speed: -1.0"), shipped.replace("turn_speed: 2.5", "turn_speed: fast")] {
+        std::fs::write(folder.join("config.ron"), broken).unwrap();
+        wait_for(&mut app, failed);
+        app.update();
+        assert_eq!(*app.world.resource::<GameConfig>(), GameConfig{turn_speed:2.5, ..default()});
+        // fixing the file applies it again
+        std::fs::write(folder.join("config.ron"), &shipped).unwrap();
+        wait_for(&mut app, |app| !failed(app));
+    }
+    std::fs::remove_dir_all(folder).unwrap();
+}
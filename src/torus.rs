@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+
+// the arena wraps around in x and z, `size` is its extent in (x, z)
+
+// shortest offset from `from` to `to`
+pub fn delta(from:Vec3, to:Vec3, size:Vec2) -> Vec3 {
+    let d = to - from;
+    Vec3::new(d.x - size.x * (d.x / size.x).round(),
+              d.y,
+              d.z - size.y * (d.z / size.y).round())
+}
+
+// the copy of `point` closest to `reference`
+pub fn nearest(point:Vec3, reference:Vec3, size:Vec2) -> Vec3 {
+    reference + delta(reference, point, size)
+}
+
+// moves `point` into the arena starting at `min`
+pub fn wrap(point:Vec3, min:Vec2, size:Vec2) -> Vec3 {
+    Vec3::new(min.x + (point.x - min.x).rem_euclid(size.x),
+              point.y,
+              min.y + (point.z - min.y).rem_euclid(size.y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN:Vec2 = Vec2::new(-11.0, -8.0);
+    const SIZE:Vec2 = Vec2::new(22.0, 16.0);
+
+    #[test]
+    fn delta_takes_the_short_way_round() {
+        assert_eq!(delta(Vec3::new(1.0, 0.0, 2.0), Vec3::new(3.0, 0.0, -1.0), SIZE), Vec3::new(2.0, 0.0, -3.0));
+        // over the x edge and over the z edge
+        assert_eq!(delta(Vec3::new(10.5, 0.0, 0.0), Vec3::new(-10.5, 0.0, 0.0), SIZE), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(delta(Vec3::new(0.0, 0.0, -7.5), Vec3::new(0.0, 0.0, 7.5), SIZE), Vec3::new(0.0, 0.0, -1.0));
+        // y doesn't wrap
+        assert_eq!(delta(Vec3::ZERO, Vec3::new(0.0, 30.0, 0.0), SIZE).y, 30.0);
+    }
+
+    #[test]
+    fn wrap_moves_points_into_the_arena() {
+        assert_eq!(wrap(Vec3::new(11.5, 1.0, -8.5), MIN, SIZE), Vec3::new(-10.5, 1.0, 7.5));
+        assert_eq!(wrap(Vec3::new(-33.0, 0.0, 24.0), MIN, SIZE), Vec3::new(-11.0, 0.0, -8.0));
+        assert_eq!(wrap(Vec3::new(3.0, 0.0, -2.0), MIN, SIZE), Vec3::new(3.0, 0.0, -2.0));
+    }
+
+    #[test]
+    fn nearest_copy_is_across_the_edge() {
+        assert_eq!(nearest(Vec3::new(-10.5, 0.0, 0.0), Vec3::new(10.0, 0.0, 0.0), SIZE), Vec3::new(11.5, 0.0, 0.0));
+    }
+}
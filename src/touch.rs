@@ -0,0 +1,229 @@
+use bevy::prelude::*;
+
+use crate::actions::{dead_zone, Action, ActionState, UpdateActions};
+use crate::config::GameConfig;
+use crate::GameState;
+
+const MARGIN:f32 = 24.0;
+const STICK_SIZE:f32 = 160.0;
+const KNOB_SIZE:f32 = 64.0;
+const BUTTON_SIZE:f32 = 96.0;
+const PAUSE_SIZE:f32 = 56.0;
+
+// on-screen joystick and buttons, shown once the screen has been touched.
+// in the wasm build the left mouse button counts as a touch as well
+pub struct TouchControlsPlugin;
+
+impl Plugin for TouchControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TouchControls>()
+            .add_startup_system(setup_touch_controls)
+            .add_system_to_stage(CoreStage::PreUpdate, touch_actions.after(UpdateActions))
+            .add_system(update_touch_controls);
+    }
+}
+
+#[derive(Resource, Default)]
+struct TouchControls{
+    detected:bool,
+    // the touch that started on the stick keeps it until released
+    stick:Option<u64>,
+    // knob position, -1..1 on both axes, y down like the window
+    offset:Vec2,
+}
+
+#[derive(Component)]
+struct TouchUi;
+
+#[derive(Component)]
+struct Knob;
+
+// window coordinates start at the top left, like touch positions
+fn stick_center(window:&Window) -> Vec2 {
+    Vec2::new(MARGIN + STICK_SIZE * 0.5, window.height() - MARGIN - STICK_SIZE * 0.5)
+}
+
+fn buttons(window:&Window) -> [(Action, Vec2, f32); 3] {
+    let right = window.width() - MARGIN - BUTTON_SIZE * 0.5;
+    let bottom = window.height() - MARGIN - BUTTON_SIZE * 0.5;
+    [
+        (Action::Thrust, Vec2::new(right - BUTTON_SIZE - MARGIN, bottom), BUTTON_SIZE),
+        (Action::Fire, Vec2::new(right, bottom - BUTTON_SIZE * 0.5), BUTTON_SIZE),
+        (Action::Pause, Vec2::new(window.width() * 0.5, MARGIN + PAUSE_SIZE * 0.5), PAUSE_SIZE),
+    ]
+}
+
+fn control(asset_server:&AssetServer, size:f32, position:UiRect, label:&str) -> (NodeBundle, TextBundle) {
+    (NodeBundle {
+        style: Style {
+            size: Size::new(Val::Px(size), Val::Px(size)),
+            position_type: PositionType::Absolute,
+            position,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        background_color: Color::rgba(0.5, 0.5, 1.0, 0.25).into(),
+        visibility: Visibility { is_visible: false },
+        ..Default::default()
+    },
+    TextBundle::from_section(
+        label,
+        TextStyle {
+            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+            font_size: 24.0,
+            color: Color::rgba(0.5, 0.5, 1.0, 0.8),
+        }
+    ))
+}
+
+fn setup_touch_controls(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+){
+    // laid out from the window edges to match stick_center and buttons
+    let layout = [
+        (BUTTON_SIZE, UiRect { right: Val::Px(2.0 * MARGIN + BUTTON_SIZE), bottom: Val::Px(MARGIN), ..Default::default() }, "Thrust"),
+        (BUTTON_SIZE, UiRect { right: Val::Px(MARGIN), bottom: Val::Px(MARGIN + BUTTON_SIZE * 0.5), ..Default::default() }, "Fire"),
+        // the margin centers it on the middle of the window
+        (PAUSE_SIZE, UiRect { left: Val::Percent(50.0), top: Val::Px(MARGIN), ..Default::default() }, "II"),
+    ];
+    for (size, position, label) in layout {
+        let (mut node, text) = control(&asset_server, size, position, label);
+        if position.left != Val::Undefined {
+            node.style.margin.left = Val::Px(-size * 0.5);
+        }
+        commands.spawn(node)
+            .with_children(|parent| {
+                parent.spawn(text);
+            })
+            .insert(TouchUi);
+    }
+
+    let (stick, _) = control(&asset_server, STICK_SIZE,
+        UiRect { left: Val::Px(MARGIN), bottom: Val::Px(MARGIN), ..Default::default() }, "");
+    commands.spawn(stick)
+        .with_children(|parent| {
+            parent.spawn(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Px(KNOB_SIZE), Val::Px(KNOB_SIZE)),
+                    position_type: PositionType::Absolute,
+                    ..Default::default()
+                },
+                background_color: Color::rgba(0.5, 0.5, 1.0, 0.5).into(),
+                ..Default::default()
+            })
+            .insert(Knob);
+        })
+        .insert(TouchUi);
+}
+
+// a finger on the screen, or the mouse standing in for one
+struct Pointer{
+    id:u64,
+    // from the top left of the window
+    position:Vec2,
+    just_pressed:bool,
+}
+
+fn pointers(window:&Window, touches:&Touches, mouse:&Input<MouseButton>) -> Vec<Pointer> {
+    let mut pointers:Vec<Pointer> = touches.iter()
+        .map(|touch| Pointer{id:touch.id(), position:touch.position(), just_pressed:touches.just_pressed(touch.id())})
+        .collect();
+    pointers.extend(mouse_pointer(window, mouse));
+    pointers
+}
+
+// mobile browsers hand taps to the wasm build as clicks of the left button,
+// touch events never reach bevy there
+fn mouse_pointer(window:&Window, mouse:&Input<MouseButton>) -> Option<Pointer> {
+    if !cfg!(target_arch = "wasm32") || !mouse.pressed(MouseButton::Left) {
+        return None;
+    }
+    // the cursor is measured from the bottom left
+    let cursor = window.cursor_position()?;
+    Some(Pointer{
+        id:u64::MAX,
+        position:Vec2::new(cursor.x, window.height() - cursor.y),
+        just_pressed:mouse.just_pressed(MouseButton::Left),
+    })
+}
+
+// a new touch anywhere, the menus go on with it
+pub fn tapped(touches:&Touches, mouse:&Input<MouseButton>) -> bool {
+    touches.iter_just_pressed().next().is_some()
+        || (cfg!(target_arch = "wasm32") && mouse.just_pressed(MouseButton::Left))
+}
+
+fn touch_actions(
+    windows: Res<Windows>,
+    touches: Res<Touches>,
+    mouse: Res<Input<MouseButton>>,
+    config: Res<GameConfig>,
+    mut controls: ResMut<TouchControls>,
+    mut actions: ResMut<ActionState>,
+){
+    let Some(window) = windows.get_primary() else { return };
+    let pointers = pointers(window, &touches, &mouse);
+    if !controls.detected && pointers.iter().any(|pointer| pointer.just_pressed) {
+        controls.detected = true;
+    }
+    if !controls.detected {
+        return;
+    }
+
+    let center = stick_center(window);
+    if controls.stick.is_some_and(|id| pointers.iter().all(|pointer| pointer.id != id)) {
+        controls.stick = None;
+    }
+    if controls.stick.is_none() {
+        controls.stick = pointers.iter()
+            .find(|pointer| pointer.just_pressed && pointer.position.distance(center) < STICK_SIZE * 0.5)
+            .map(|pointer| pointer.id);
+    }
+    let offset = match pointers.iter().find(|pointer| Some(pointer.id) == controls.stick) {
+        Some(pointer) => ((pointer.position - center) / (STICK_SIZE * 0.5)).clamp_length_max(1.0),
+        None => Vec2::ZERO,
+    };
+    controls.offset = offset;
+    let turn = dead_zone(offset.x, config.stick_dead_zone);
+    actions.press(if offset.x < 0.0 { Action::RotateLeft } else { Action::RotateRight }, turn);
+
+    for (action, button, size) in buttons(window) {
+        for pointer in pointers.iter() {
+            let inside = (pointer.position - button).abs().max_element() < size * 0.5;
+            if inside && Some(pointer.id) != controls.stick {
+                actions.press(action, 1.0);
+                if pointer.just_pressed {
+                    actions.trigger(action);
+                }
+            }
+        }
+    }
+}
+
+fn update_touch_controls(
+    state: Res<State<GameState>>,
+    controls: Res<TouchControls>,
+    mut query_ui: Query<&mut Visibility, With<TouchUi>>,
+    mut query_knob: Query<&mut Style, With<Knob>>,
+){
+    let visible = controls.detected && matches!(state.current(), GameState::Playing | GameState::Paused);
+    for mut visibility in query_ui.iter_mut() {
+        if visibility.is_visible != visible {
+            visibility.is_visible = visible;
+        }
+    }
+    let travel = (STICK_SIZE - KNOB_SIZE) * 0.5;
+    let position = UiRect {
+        left: Val::Px(travel + controls.offset.x * travel),
+        top: Val::Px(travel + controls.offset.y * travel),
+        ..Default::default()
+    };
+    // touching the style relayouts the ui, only do it when the knob moved
+    for mut style in query_knob.iter_mut() {
+        if style.position != position {
+            style.position = position;
+        }
+    }
+}